@@ -0,0 +1,347 @@
+//! Typing entry fields into another window, once it is checked against the entry.
+
+use crate::cli;
+use crate::config;
+use crate::otp;
+use crate::pass;
+use crate::prefill;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::thread;
+use std::time;
+
+const SECOND_LEVEL_SUFFIXES: &[&str] = &["co", "com", "net", "org", "ac", "gov", "edu", "ne", "or"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutofillStep {
+    Text(String),
+    /// A key, by its xdotool name, e.g. `Tab`.
+    Key(String),
+    /// A pause, in milliseconds.
+    Delay(u64),
+    /// An otp code, calculated when it is typed, as it may expire during
+    /// the steps before it.
+    Otp(String, otp::OtpEncoder),
+}
+
+impl AutofillStep {
+    /// The otp step of an entry, failing if its secret is missing or invalid.
+    pub fn otp(entry: &pass::PassEntry) -> Result<Self, Error> {
+        let (secret, encoder) = otp::entry_otp_secret(entry)?;
+        otp::calculate_otp_with_encoder(&secret, encoder)
+            .context("Failed to calculate otp from secret")?;
+        Ok(AutofillStep::Otp(secret, encoder))
+    }
+}
+
+/// The keys of autotype sequences, by placeholder, with their xdotool names.
+const KEYS: &[(&str, &str)] = &[
+    ("TAB", "Tab"),
+    ("ENTER", "Return"),
+    ("SPACE", "space"),
+    ("ESCAPE", "Escape"),
+    ("BACKSPACE", "BackSpace"),
+];
+
+/// The steps of an autotype sequence, such as
+/// `{username} {TAB} {password} {ENTER} {DELAY 500} {otp}`, with the entry's
+/// fields filled in, so that nothing is typed if any placeholder is unknown.
+///
+/// Uppercase placeholders are keys, and `DELAY` a pause in milliseconds. The
+/// others are fields: `password`, `otp` for a code from the entry's secret, calculated as it is typed,
+/// `username`, which is the `email` field in entries without a username, or
+/// any other key.
+pub fn parse_sequence(sequence: &str, entry: &pass::PassEntry) -> Result<Vec<AutofillStep>, Error> {
+    let mut steps = Vec::new();
+    let mut rest = sequence.trim_start();
+    while !rest.is_empty() {
+        let placeholder = rest.strip_prefix('{').ok_or_else(|| {
+            anyhow!(
+                "Unexpected text {:?}, placeholders are written in braces",
+                rest
+            )
+        })?;
+        let (placeholder, after) = placeholder
+            .split_once('}')
+            .ok_or_else(|| anyhow!("Unclosed placeholder {{{}", placeholder))?;
+        steps.push(parse_placeholder(placeholder.trim(), entry)?);
+        rest = after.trim_start();
+    }
+    Ok(steps)
+}
+
+fn parse_placeholder(placeholder: &str, entry: &pass::PassEntry) -> Result<AutofillStep, Error> {
+    if let Some(delay) = placeholder.strip_prefix("DELAY ") {
+        return Ok(AutofillStep::Delay(delay.trim().parse().with_context(
+            || format!("Invalid delay {{{}}}, expected milliseconds", placeholder),
+        )?));
+    }
+    if let Some((_, key)) = KEYS.iter().find(|(name, _)| *name == placeholder) {
+        return Ok(AutofillStep::Key(key.to_string()));
+    }
+    if placeholder == "otp" {
+        return AutofillStep::otp(entry);
+    }
+
+    let value = match placeholder {
+        "password" => entry.get_password(),
+        "username" => entry
+            .get_value_with_key("username")
+            .or_else(|| entry.get_value_with_key("email")),
+        key => entry.get_value_with_key(key),
+    };
+    match value {
+        Some(val) => Ok(AutofillStep::Text(val)),
+        None if placeholder.chars().all(|c| !c.is_lowercase()) => Err(anyhow!(
+            "Unknown placeholder {{{}}}, the keys are {} and DELAY",
+            placeholder,
+            KEYS.iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+        None => Err(anyhow!(
+            "Unknown placeholder {{{}}}, the entry has no {} field",
+            placeholder,
+            placeholder
+        )),
+    }
+}
+
+/// Types the steps into the target window, unless it doesn't match the entry
+/// and autofilling into it isn't confirmed.
+pub fn autofill(
+    entry: &pass::PassEntry,
+    steps: &[AutofillStep],
+    config: &cli::Config,
+) -> Result<(), Error> {
+    let window_id = target_window_id(config)?;
+    if !check_target_window(entry, &window_id, config)? {
+        return Ok(());
+    }
+
+    for step in steps {
+        match step {
+            AutofillStep::Text(text) => config
+                .display
+                .type_string_in_window(&window_id, text)
+                .context("Failed to type text in window")?,
+            AutofillStep::Key(key) => config
+                .display
+                .type_key_in_window(&window_id, key)
+                .with_context(|| format!("Failed to type {} key in window", key))?,
+            AutofillStep::Delay(millis) => thread::sleep(time::Duration::from_millis(*millis)),
+            AutofillStep::Otp(secret, encoder) => config
+                .display
+                .type_string_in_window(
+                    &window_id,
+                    &otp::calculate_otp_with_encoder(secret, *encoder)
+                        .context("Failed to calculate otp from secret")?,
+                )
+                .context("Failed to type otp in window")?,
+        }
+    }
+    Ok(())
+}
+
+/// The window which autofill types into: the one which was active before the
+/// menus opened, or else one clicked by the user.
+fn target_window_id(config: &cli::Config) -> Result<String, Error> {
+    match (config.file_config.autofill.target, &config.target_window) {
+        (config::AutofillTarget::Previous, Some(window_id)) => Ok(window_id.to_owned()),
+        _ => config
+            .display
+            .get_window_id_by_user_select()
+            .context("Failed to get window_id by user selection"),
+    }
+}
+
+/// Whether to autofill into the window, asking when it doesn't match the entry.
+fn check_target_window(
+    entry: &pass::PassEntry,
+    window_id: &str,
+    config: &cli::Config,
+) -> Result<bool, Error> {
+    let autofill_config = &config.file_config.autofill;
+    let class_names = config
+        .display
+        .get_window_class(window_id)
+        .context("Failed to read the window class")?;
+
+    if let Some(class_name) = class_names.iter().find(|class_name| {
+        autofill_config
+            .confirm_classes
+            .iter()
+            .any(|class| class.eq_ignore_ascii_case(class_name))
+    }) {
+        return config.launcher.confirm(
+            &format!("autofill {} into {}?", entry.path, class_name),
+            &format!("{} windows always need confirmation", class_name),
+        );
+    }
+
+    let domain = match entry
+        .get_value_with_key("url")
+        .and_then(|url| prefill::domain(&url))
+    {
+        Some(val) => val,
+        None => return Ok(true),
+    };
+    let title = match config
+        .display
+        .get_window_name(window_id)
+        .context("Failed to read the window title")?
+    {
+        Some(title) => title,
+        // wayland compositors without IPC don't tell the focused window's title
+        None => return Ok(true),
+    };
+    let window = class_names.last().unwrap_or(&title);
+    if autofill_config.url_mismatch == config::UrlMismatch::Allow
+        || window_matches_domain(&title, &class_names, &domain)
+    {
+        return Ok(true);
+    }
+
+    match autofill_config.url_mismatch {
+        config::UrlMismatch::Refuse => Err(anyhow!(
+            "Refused to autofill {}: the window {:?} doesn't match {}",
+            entry.path,
+            title,
+            domain
+        )),
+        _ => config.launcher.confirm(
+            &format!("autofill {} into {}?", entry.path, window),
+            &format!("the window {:?} doesn't match {}", title, domain),
+        ),
+    }
+}
+
+/// Whether the window's title or class names the domain, or the site: the
+/// domain's name before its suffix, e.g. `github` of `gist.github.com`, as a
+/// word of the title. A page can set any title, so this only catches the
+/// wrong window being focused by mistake.
+fn window_matches_domain(title: &str, class_names: &[String], domain: &str) -> bool {
+    let title = title.to_lowercase();
+    // the domain mustn't be part of a longer name, as in `evilbank.com` or `bank.com.evil.net`
+    let is_name_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '.');
+    if title.match_indices(domain).any(|(start, _)| {
+        let before = title[..start].chars().next_back();
+        let after = title[start + domain.len()..].chars().next();
+        !is_name_char(before) && before != Some('-') && !is_name_char(after)
+    }) {
+        return true;
+    }
+
+    let mut labels = domain.split('.').collect::<Vec<_>>();
+    let tld = labels.pop().unwrap_or("");
+    // second level suffixes of country domains, such as `co` of `example.co.uk`
+    if tld.len() == 2
+        && labels.len() > 1
+        && labels
+            .last()
+            .is_some_and(|label| SECOND_LEVEL_SUFFIXES.contains(label))
+    {
+        labels.pop();
+    }
+    let site = match labels.last() {
+        // short names are found in too many titles
+        Some(site) if site.len() >= 3 => site,
+        _ => return false,
+    };
+
+    title
+        .split(|c: char| !c.is_alphanumeric() && c != '-' && c != '.')
+        .any(|word| word.trim_end_matches('.') == *site)
+        || class_names
+            .iter()
+            .any(|class_name| class_name.eq_ignore_ascii_case(site))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autotype_sequences() {
+        let entry = pass::PassEntry {
+            path: "web/example.com".to_owned(),
+            fields: vec![
+                pass::PassEntryField::Password("hunter2".to_owned()),
+                pass::PassEntryField::KeyVal("email".to_owned(), "alice@example.com".to_owned()),
+                pass::PassEntryField::KeyVal("pin".to_owned(), "1234".to_owned()),
+            ],
+        };
+        let text = |text: &str| AutofillStep::Text(text.to_owned());
+        let key = |key: &str| AutofillStep::Key(key.to_owned());
+
+        assert_eq!(
+            parse_sequence(
+                "{username} {ENTER}{DELAY 500}\n{ password } {TAB} {pin} {ENTER}",
+                &entry
+            )
+            .unwrap(),
+            [
+                text("alice@example.com"),
+                key("Return"),
+                AutofillStep::Delay(500),
+                text("hunter2"),
+                key("Tab"),
+                text("1234"),
+                key("Return"),
+            ]
+        );
+
+        for sequence in [
+            "{username} {TABB}",
+            "{username} {cvv}",
+            "{DELAY soon}",
+            "{username} {TAB",
+            "username {TAB}",
+        ] {
+            assert!(parse_sequence(sequence, &entry).is_err(), "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn windows_matching_domains() {
+        let firefox = ["Navigator".to_owned(), "firefox".to_owned()];
+        for (title, domain, matches) in [
+            (
+                "Sign in to GitHub · GitHub — Mozilla Firefox",
+                "github.com",
+                true,
+            ),
+            ("Gist · GitHub — Mozilla Firefox", "gist.github.com", true),
+            ("Log in | Example — Mozilla Firefox", "example.co.uk", true),
+            (
+                "Sign in to GitHub · GitHub — Mozilla Firefox",
+                "gitlab.com",
+                false,
+            ),
+            ("Box — Mozilla Firefox", "app.box.com", true),
+            ("Fox News — Mozilla Firefox", "fox.com.au", true),
+            ("New Tab — Mozilla Firefox", "fox.com", false),
+            ("x.com — Mozilla Firefox", "x.com", true),
+            ("Home / X — Mozilla Firefox", "x.com", false),
+            ("bank.com — Mozilla Firefox", "bank.com", true),
+            ("Welcome to Bank. — Mozilla Firefox", "bank.com", true),
+            ("evilbank.com — Mozilla Firefox", "bank.com", false),
+            ("my-bank.com — Mozilla Firefox", "bank.com", false),
+            ("bank.com.attacker.net — Mozilla Firefox", "bank.com", false),
+            ("login.bank.com — Mozilla Firefox", "bank.com", false),
+        ] {
+            assert_eq!(
+                window_matches_domain(title, &firefox, domain),
+                matches,
+                "{:?} {:?}",
+                title,
+                domain
+            );
+        }
+
+        let slack = ["slack".to_owned(), "Slack".to_owned()];
+        assert!(window_matches_domain("general - Acme", &slack, "slack.com"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Commands on several entries at once, which are selected in the main menu
+//! with the launcher's multi-select.
+
+use crate::cli;
+use crate::favourites;
+use crate::launcher;
+use crate::menu;
+use crate::pass;
+use anyhow::anyhow;
+use anyhow::Error;
+use std::env;
+
+pub fn run_bulk_command(
+    command: &menu::MainMenuCommand,
+    entry_paths: &[String],
+    config: &cli::Config,
+) -> Result<(), Error> {
+    match command {
+        menu::MainMenuCommand::MoveEntries => move_entries(entry_paths, config),
+        menu::MainMenuCommand::DeleteEntries => delete_entries(entry_paths, config),
+        menu::MainMenuCommand::ReencryptEntries => reencrypt_entries(entry_paths, config),
+        menu::MainMenuCommand::ExportEntries => export_entries(entry_paths, config),
+        command => Err(anyhow!("{:?} is not a bulk command", command)),
+    }
+}
+
+fn describe(entry_paths: &[String]) -> String {
+    match entry_paths.len() {
+        1 => "1 entry".to_owned(),
+        count => format!("{} entries", count),
+    }
+}
+
+/// Runs an action on every entry, carrying on past failures, which are
+/// reported together at the end with the entries they happened to.
+fn for_each_entry(
+    verb: &str,
+    entry_paths: &[String],
+    mut action: impl FnMut(&str) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let failures = entry_paths
+        .iter()
+        .filter_map(|entry_path| {
+            action(entry_path)
+                .err()
+                .map(|error| format!("{}: {:#}", entry_path, error))
+        })
+        .collect::<Vec<_>>();
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Failed to {} {} of {}:\n{}",
+            verb,
+            failures.len(),
+            describe(entry_paths),
+            failures.join("\n")
+        )),
+    }
+}
+
+/// Moves entries into a folder, keeping their names.
+fn move_entries(entry_paths: &[String], config: &cli::Config) -> Result<(), Error> {
+    let current_folder = entry_paths
+        .first()
+        .and_then(|entry_path| entry_path.rsplit_once('/'))
+        .map_or("", |(folder, _)| folder);
+    let folder = match config.launcher.get_input(
+        launcher::Dialog::Editor,
+        "move to folder",
+        current_folder,
+        &format!("moving {}", describe(entry_paths)),
+    )? {
+        Some(val) => val.trim().trim_matches('/').to_owned(),
+        None => return Ok(()),
+    };
+    let destination = match folder.is_empty() {
+        true => "the top folder".to_owned(),
+        false => format!("{}/", folder),
+    };
+    if !config.launcher.confirm(
+        &format!("move {} to {}?", describe(entry_paths), destination),
+        &entry_paths.join("\n"),
+    )? {
+        return Ok(());
+    }
+
+    let mut favourites = favourites::Favourites::load(&config.pass_store_path)?;
+    for_each_entry("move", entry_paths, |entry_path| {
+        let name = entry_path.rsplit('/').next().unwrap_or(entry_path);
+        let new_entry_path = match folder.is_empty() {
+            true => name.to_owned(),
+            false => format!("{}/{}", folder, name),
+        };
+        if new_entry_path == entry_path {
+            return Ok(());
+        }
+        pass::PassStoreDirectory::move_entry(&config.pass_store_path, entry_path, &new_entry_path)?;
+
+        // favourites stay pinned when they are moved
+        if favourites.contains(entry_path) {
+            favourites.toggle(entry_path)?;
+            favourites.toggle(&new_entry_path)?;
+        }
+        Ok(())
+    })
+}
+
+fn delete_entries(entry_paths: &[String], config: &cli::Config) -> Result<(), Error> {
+    if !config.launcher.confirm(
+        &format!("delete {}?", describe(entry_paths)),
+        &entry_paths.join("\n"),
+    )? {
+        return Ok(());
+    }
+    for_each_entry(
+        "delete",
+        entry_paths,
+        pass::PassStoreDirectory::remove_entry,
+    )
+}
+
+/// Decrypts and inserts entries again, which encrypts them for the keys
+/// currently in the store's `.gpg-id`.
+fn reencrypt_entries(entry_paths: &[String], config: &cli::Config) -> Result<(), Error> {
+    if !config.launcher.confirm(
+        &format!("re-encrypt {}?", describe(entry_paths)),
+        &entry_paths.join("\n"),
+    )? {
+        return Ok(());
+    }
+    for_each_entry("re-encrypt", entry_paths, |entry_path| {
+        pass::PassEntry::from_path(entry_path, &*config.launcher)?.insert_into_store()
+    })
+}
+
+/// Copies the encrypted files of entries to another directory, as a
+/// password store of only those entries.
+fn export_entries(entry_paths: &[String], config: &cli::Config) -> Result<(), Error> {
+    let export_path = match config.launcher.get_input(
+        launcher::Dialog::Editor,
+        "export to directory",
+        "~/password-store-export",
+        &format!("exporting {}, still encrypted", describe(entry_paths)),
+    )? {
+        Some(val) => val.trim().to_owned(),
+        None => return Ok(()),
+    };
+    let export_path = match (export_path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(path), Ok(home)) => format!("{}/{}", home, path),
+        _ => export_path,
+    };
+
+    for_each_entry("export", entry_paths, |entry_path| {
+        pass::PassStoreDirectory::export_entry(&config.pass_store_path, entry_path, &export_path)
+    })
+}
@@ -1,3 +1,6 @@
+use crate::config;
+use crate::display;
+use crate::launcher;
 use crate::pass;
 use anyhow::Error;
 use structopt::StructOpt;
@@ -13,6 +16,14 @@ struct CliConfig {
     #[structopt(long)]
     no_notify: bool,
 
+    /// Sets the program which draws the menus
+    #[structopt(
+        long,
+        default_value = "rofi",
+        possible_values = launcher::LauncherKind::VARIANTS
+    )]
+    launcher: launcher::LauncherKind,
+
     /// Sets the rofi matching method
     #[structopt(
         long,
@@ -24,27 +35,116 @@ struct CliConfig {
     /// Overrides the default password storage directory
     #[structopt(long, env = "PASSWORD_STORE_DIR")]
     password_store_dir: Option<String>,
+
+    /// Overrides the default configuration file
+    #[structopt(long, env = "PASS_ROFI_GUI_CONFIG")]
+    config: Option<String>,
+
+    /// Disables the usage log, which orders the main menu by frecency
+    #[structopt(long)]
+    no_usage_log: bool,
+
+    /// Removes old records, and those of deleted entries, from the usage log
+    #[structopt(long)]
+    prune_usage_log: bool,
+
+    /// Sets the initial search query of the main menu, instead of taking one
+    /// from the active window
+    #[structopt(long)]
+    query: Option<String>,
+
+    /// Sets the window which autofill types into, instead of the active window
+    #[structopt(long)]
+    target_window: Option<String>,
+
+    /// Runs the menus in a single rofi window, as a rofi script mode
+    #[structopt(long)]
+    modi: bool,
+
+    /// A command handed over from rofi script mode
+    #[structopt(long, hidden = true)]
+    modi_deferred: Option<String>,
+
+    /// Serves copied data on the clipboard in the background
+    #[structopt(long, hidden = true)]
+    serve_clipboard: bool,
+
+    /// The row selected in rofi script mode, passed by rofi
+    #[structopt(hidden = true)]
+    rofi_selection: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct Config {
     pub browser: Option<String>,
     pub no_notify: bool,
+    pub launcher: Box<dyn launcher::Launcher>,
+    pub launcher_kind: launcher::LauncherKind,
+    pub display: Box<dyn display::DisplayServer>,
     pub rofi_matching: String,
     pub pass_store_path: String,
+    pub file_config: config::FileConfig,
+    pub usage_log: bool,
+    pub prune_usage_log: bool,
+    pub query: Option<String>,
+    /// The window which autofill types into, unless it is set to select one.
+    pub target_window: Option<String>,
+    pub modi: bool,
+    pub modi_deferred: Option<String>,
+    pub serve_clipboard: bool,
+    pub rofi_selection: Option<String>,
 }
 
 impl Config {
     pub fn new() -> Result<Self, Error> {
         let cli_config = CliConfig::from_args();
+        let file_config = config::FileConfig::from_path(
+            &config::FileConfig::calculate_config_path(&cli_config.config)?,
+        )?;
+        let display = display::detect();
+        let target_window = target_window(&cli_config, &file_config, &*display);
 
         Ok(Self {
             browser: cli_config.browser,
             no_notify: cli_config.no_notify,
+            launcher: cli_config
+                .launcher
+                .build(&cli_config.rofi_matching, &file_config)?,
+            launcher_kind: cli_config.launcher,
+            display,
             rofi_matching: cli_config.rofi_matching,
             pass_store_path: pass::PassStoreDirectory::calculate_pass_store_path(
                 &cli_config.password_store_dir,
             )?,
+            file_config,
+            usage_log: !cli_config.no_usage_log,
+            prune_usage_log: cli_config.prune_usage_log,
+            query: cli_config.query,
+            target_window,
+            modi: cli_config.modi,
+            modi_deferred: cli_config.modi_deferred,
+            serve_clipboard: cli_config.serve_clipboard,
+            rofi_selection: cli_config.rofi_selection,
         })
     }
 }
+
+/// The window which was active before the menus opened. Script mode and the
+/// commands it hands over are run while rofi is active, so they are passed
+/// the window which `--modi` found instead, and fzf runs in the active window.
+fn target_window(
+    cli_config: &CliConfig,
+    file_config: &config::FileConfig,
+    display: &dyn display::DisplayServer,
+) -> Option<String> {
+    if cli_config.target_window.is_some()
+        || file_config.autofill.target != config::AutofillTarget::Previous
+        || cli_config.launcher == launcher::LauncherKind::Fzf
+        || cli_config.modi_deferred.is_some()
+        || cli_config.serve_clipboard
+        || std::env::var_os("ROFI_RETV").is_some()
+    {
+        return cli_config.target_window.clone();
+    }
+    display.get_active_window_id().ok()
+}
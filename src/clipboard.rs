@@ -0,0 +1,312 @@
+//! Copying to the clipboard, which a copy of pass-rofi-gui left running with
+//! `--serve-clipboard` clears again after a while, like `pass show --clip`.
+
+use crate::cli;
+use crate::display;
+use crate::otp;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use notify_rust::Notification;
+use serde::Deserialize;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time;
+
+const DEFAULT_CLEAR_AFTER: u64 = 45;
+
+/// What the clipboard server is handed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clip {
+    pub copied: String,
+    /// Data copied in turn once the data before it has been pasted.
+    pub queue: Vec<ClipText>,
+    /// The contents to restore, or `None` to leave the clipboard empty.
+    pub previous: Option<String>,
+    /// Seconds until the copied data is cleared, or `None` to keep it.
+    pub clear_after: Option<u64>,
+    /// Clears the copied data once it has been pasted.
+    pub paste_once: bool,
+}
+
+/// Queued data, which is worked out when it is pasted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClipText {
+    Text(String),
+    /// An otp code, calculated when it is pasted, as it may expire while the
+    /// data before it is pasted.
+    Otp(String, otp::OtpEncoder),
+}
+
+impl ClipText {
+    pub fn text(&self) -> Result<String, Error> {
+        match self {
+            ClipText::Text(text) => Ok(text.to_owned()),
+            ClipText::Otp(secret, encoder) => otp::calculate_otp_with_encoder(secret, *encoder)
+                .context("Failed to calculate otp from secret"),
+        }
+    }
+}
+
+/// Copies data to the clipboard, to be cleared after `[clipboard] clear_after`
+/// or `$PASSWORD_STORE_CLIP_TIME` seconds.
+pub fn copy(data: &str, config: &cli::Config) -> Result<(), Error> {
+    copy_clip(data, Vec::new(), config)
+}
+
+/// Copies several values to the clipboard, each once the one before has been
+/// pasted, for apps which can't be autofilled. The clipboard is cleared after
+/// the last one is pasted, or the timeout. Only supported on X11.
+pub fn copy_queue(data: &[ClipText], config: &cli::Config) -> Result<(), Error> {
+    let (first, rest) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("Nothing to copy"))?;
+    copy_clip(&first.text()?, rest.to_vec(), config)
+}
+
+fn copy_clip(data: &str, queue: Vec<ClipText>, config: &cli::Config) -> Result<(), Error> {
+    let clear_after = Some(clear_after(config)).filter(|clear_after| *clear_after != 0);
+    let paste_once = config.file_config.clipboard.paste_once || !queue.is_empty();
+    let copied = match queue.len() {
+        0 => "copied".to_owned(),
+        len => format!("copied {} values to paste in turn", len + 1),
+    };
+
+    let previous = match take_over_server() {
+        Some(restore) => restore,
+        None => config.display.get_clipboard().ok(),
+    };
+    spawn_server(
+        &Clip {
+            copied: data.to_owned(),
+            queue,
+            previous,
+            clear_after,
+            paste_once,
+        },
+        config,
+    )?;
+
+    let summary = match (clear_after, paste_once) {
+        (Some(clear_after), true) => {
+            format!("{}, clears once pasted or in {}s", copied, clear_after)
+        }
+        (Some(clear_after), false) => format!("{}, clears in {}s", copied, clear_after),
+        (None, true) => format!("{}, clears once pasted", copied),
+        (None, false) => return Ok(()),
+    };
+    if !config.no_notify {
+        Notification::new()
+            .appname("pass-rofi-gui")
+            .summary(&summary)
+            .timeout(2000)
+            .show()
+            .context("Failed to show desktop notification")?;
+    }
+    Ok(())
+}
+
+fn clear_after(config: &cli::Config) -> u64 {
+    config
+        .file_config
+        .clipboard
+        .clear_after
+        .or_else(|| {
+            env::var("PASSWORD_STORE_CLIP_TIME")
+                .ok()
+                .and_then(|val| val.parse().ok())
+        })
+        .unwrap_or(DEFAULT_CLEAR_AFTER)
+}
+
+/// Starts the clipboard server, returning once it holds the copied data.
+fn spawn_server(clip: &Clip, config: &cli::Config) -> Result<(), Error> {
+    let clip = serde_json::to_string(clip).context("Failed to serialize clipboard data")?;
+
+    // the server is started like this run, with the same launcher and
+    // configuration, but without the row rofi appends in script mode
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    if config.rofi_selection.is_some() {
+        args.pop();
+    }
+
+    let mut child =
+        process::Command::new(env::current_exe().context("Failed to find current executable")?)
+            .args(args)
+            .arg("--serve-clipboard")
+            .env_remove("ROFI_RETV")
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .stderr(process::Stdio::null())
+            .spawn()
+            .context("Failed to spawn clipboard server")?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| anyhow!("Failed to open clipboard server stdin"))?
+        .write_all(clip.as_bytes())
+        .context("Failed to write to clipboard server stdin")?;
+
+    let mut status = String::new();
+    io::BufReader::new(
+        child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to open clipboard server stdout"))?,
+    )
+    .read_line(&mut status)
+    .context("Failed to read clipboard server stdout")?;
+    match status.trim_end() {
+        "copied" => Ok(()),
+        "" => Err(anyhow!("Failed to copy to the clipboard")),
+        error => Err(anyhow!("Failed to copy to the clipboard: {}", error)),
+    }
+}
+
+/// The socket of the running clipboard server, which only the user can connect to.
+/// The socket where the next copy takes over from the running server, in
+/// `$XDG_RUNTIME_DIR` if only the user can access it, or else `None`.
+fn socket_path() -> Option<PathBuf> {
+    let directory = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    let metadata = fs::metadata(&directory).ok()?;
+    match metadata.uid() == rustix::process::getuid().as_raw() && metadata.mode() & 0o077 == 0 {
+        true => Some(directory.join("pass-rofi-gui-clipboard")),
+        false => None,
+    }
+}
+
+fn is_same_user(stream: &UnixStream) -> bool {
+    rustix::net::sockopt::socket_peercred(stream)
+        .is_ok_and(|cred| cred.uid == rustix::process::getuid())
+}
+
+/// Stops the running clipboard server, if there is one, returning the text
+/// which it would have restored.
+fn take_over_server() -> Option<Option<String>> {
+    let mut stream = UnixStream::connect(socket_path()?).ok()?;
+    if !is_same_user(&stream) {
+        return None;
+    }
+    let mut restore = String::new();
+    // the server exits once it has answered, which closes the stream
+    stream.read_to_string(&mut restore).ok()?;
+    serde_json::from_str(&restore).ok()
+}
+
+/// Listens for the next copy taking over, to answer it with the text to
+/// restore and exit.
+fn listen_for_take_over(clip: &Clip) -> Result<(), Error> {
+    let path = match socket_path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    // left behind by a server which exited, or was already taken over by `copy`
+    let _ = fs::remove_file(&path);
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to listen on {:?}", path))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to set permissions of {:?}", path))?;
+
+    let clip = clip.clone();
+    thread::spawn(move || -> Result<(), Error> {
+        let mut stream = loop {
+            let (stream, _) = listener.accept()?;
+            if is_same_user(&stream) {
+                break stream;
+            }
+        };
+        let restore = restore_text(&*display::detect(), &clip);
+        stream.write_all(serde_json::to_string(&restore)?.as_bytes())?;
+        process::exit(0);
+    });
+    Ok(())
+}
+
+/// The text to restore once the copied data is cleared: the previous
+/// contents if the clipboard still holds the copied data, or else what was
+/// copied since.
+fn restore_text<TDisplay: display::DisplayServer + ?Sized>(
+    display: &TDisplay,
+    clip: &Clip,
+) -> Option<String> {
+    match display.get_clipboard().ok() {
+        Some(current)
+            if current != clip.copied
+                && !clip
+                    .queue
+                    .iter()
+                    .any(|queued| queued.text().ok().as_ref() == Some(&current)) =>
+        {
+            Some(current)
+        }
+        Some(_) => clip.previous.clone(),
+        // the clipboard is empty, or not text
+        None => None,
+    }
+}
+
+/// Serves the clipboard, as handed over by `copy`.
+pub fn run_server(config: &cli::Config) -> Result<(), Error> {
+    let mut clip = String::new();
+    io::stdin()
+        .read_to_string(&mut clip)
+        .context("Failed to read clipboard data")?;
+    let clip: Clip = serde_json::from_str(&clip).context("Failed to parse clipboard data")?;
+
+    // tells `copy` that the data is on the clipboard
+    let mut ready = || {
+        println!("copied");
+    };
+    let result = listen_for_take_over(&clip)
+        .and_then(|()| config.display.serve_clipboard(&clip, &mut ready))
+        .inspect_err(|error| println!("{:#}", error));
+    if let Some(path) = socket_path() {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+/// Waits until the copied data is to be cleared, then clears it if it is still
+/// on the clipboard, for display servers which can't tell when it is replaced.
+pub fn clear_after_timeout<TDisplay: display::DisplayServer + ?Sized>(
+    display: &TDisplay,
+    clip: &Clip,
+) -> Result<(), Error> {
+    let clear_after = match clip.clear_after {
+        Some(val) => val,
+        None => return Ok(()),
+    };
+    thread::sleep(time::Duration::from_secs(clear_after));
+
+    // something else was copied since
+    if display.get_clipboard().ok().as_ref() != Some(&clip.copied) {
+        return Ok(());
+    }
+    match &clip.previous {
+        Some(previous) => display.copy_to_clipboard(previous),
+        None => display.clear_clipboard(),
+    }
+}
+
+/// Fails for queues, on display servers which can only copy a single value.
+pub fn check_no_queue(clip: &Clip) -> Result<(), Error> {
+    match clip.queue.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow!(
+            "Copying values to paste in turn is only supported on X11"
+        )),
+    }
+}
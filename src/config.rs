@@ -0,0 +1,437 @@
+use crate::launcher;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+
+/// The optional configuration file, at `$XDG_CONFIG_HOME/pass-rofi-gui/config.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub rofi: RofiConfig,
+    pub dialogs: DialogsConfig,
+    pub icons: IconsConfig,
+    pub entry_menu: EntryMenuConfig,
+    pub keybindings: KeybindingsConfig,
+    pub main_menu: MainMenuConfig,
+    pub prefill: PrefillConfig,
+    pub autofill: AutofillConfig,
+    pub clipboard: ClipboardConfig,
+}
+
+impl FileConfig {
+    pub fn calculate_config_path(custom_path: &Option<String>) -> Result<String, Error> {
+        if let Some(val) = custom_path {
+            return Ok(val.to_owned());
+        }
+        match (env::var("XDG_CONFIG_HOME"), env::var("HOME")) {
+            (Ok(val), _) if !val.is_empty() => Ok(format!("{}/pass-rofi-gui/config.toml", val)),
+            (_, Ok(val)) => Ok(format!("{}/.config/pass-rofi-gui/config.toml", val)),
+            _ => Err(anyhow!(
+                "Can't find config file! Please set $XDG_CONFIG_HOME or $HOME"
+            )),
+        }
+    }
+
+    /// Reads the configuration file, falling back to the defaults if it doesn't exist.
+    pub fn from_path(config_path: &str) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(config_path) {
+            Ok(val) => val,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {:?}", config_path))
+            }
+        };
+
+        Self::from_toml(&contents).with_context(|| format!("Failed to parse {:?}", config_path))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// Arguments passed to rofi. The top level applies to every dialog, and each
+/// dialog's table is applied after it.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RofiConfig {
+    pub theme: Option<String>,
+    pub theme_str: Option<String>,
+    pub args: Vec<String>,
+
+    pub main_menu: RofiDialogConfig,
+    pub entry_menu: RofiDialogConfig,
+    pub passphrase: RofiDialogConfig,
+    pub editor: RofiDialogConfig,
+    pub confirm: RofiDialogConfig,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RofiDialogConfig {
+    pub theme: Option<String>,
+    pub theme_str: Option<String>,
+    pub args: Vec<String>,
+}
+
+impl RofiConfig {
+    pub fn dialog(&self, dialog: launcher::Dialog) -> &RofiDialogConfig {
+        match dialog {
+            launcher::Dialog::MainMenu => &self.main_menu,
+            launcher::Dialog::EntryMenu => &self.entry_menu,
+            launcher::Dialog::Passphrase => &self.passphrase,
+            launcher::Dialog::Editor => &self.editor,
+            launcher::Dialog::Confirm => &self.confirm,
+        }
+    }
+
+    /// The theme arguments for a dialog, to be placed after the built-in arguments.
+    pub fn args(&self, dialog: launcher::Dialog) -> Vec<String> {
+        let dialog_config = self.dialog(dialog);
+        let mut args = Vec::new();
+
+        // the dialog's theme file replaces the global one
+        if let Some(theme) = dialog_config.theme.as_ref().or(self.theme.as_ref()) {
+            args.push("-theme".to_owned());
+            args.push(theme.to_owned());
+        }
+        // but theme strings are layered, so that both apply
+        for theme_str in self.theme_str.iter().chain(dialog_config.theme_str.iter()) {
+            args.push("-theme-str".to_owned());
+            args.push(theme_str.to_owned());
+        }
+        args.extend(self.args.iter().cloned());
+        args.extend(dialog_config.args.iter().cloned());
+
+        args
+    }
+}
+
+/// Prompt and help text overrides, which apply to every launcher.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DialogsConfig {
+    pub main_menu: DialogConfig,
+    pub entry_menu: DialogConfig,
+    pub passphrase: DialogConfig,
+    pub editor: DialogConfig,
+    pub confirm: DialogConfig,
+}
+
+impl DialogsConfig {
+    pub fn dialog(&self, dialog: launcher::Dialog) -> &DialogConfig {
+        match dialog {
+            launcher::Dialog::MainMenu => &self.main_menu,
+            launcher::Dialog::EntryMenu => &self.entry_menu,
+            launcher::Dialog::Passphrase => &self.passphrase,
+            launcher::Dialog::Editor => &self.editor,
+            launcher::Dialog::Confirm => &self.confirm,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DialogConfig {
+    /// Replaces the prompt. `{prompt}` is substituted with the default prompt.
+    pub prompt: Option<String>,
+    /// Replaces the help text (the keybindings in the menus). Empty to hide it.
+    pub help: Option<String>,
+}
+
+impl DialogConfig {
+    pub fn prompt(&self, default: &str) -> String {
+        match &self.prompt {
+            Some(prompt) => prompt.replace("{prompt}", default),
+            None => default.to_owned(),
+        }
+    }
+
+    pub fn help(&self, default: &str) -> String {
+        match &self.help {
+            Some(help) => help.to_owned(),
+            None => default.to_owned(),
+        }
+    }
+}
+
+/// Icons for the entries of the main menu, see the `icons` module.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconsConfig {
+    /// Use the `icon:` field of entries which have been opened before.
+    pub fields: bool,
+    /// The icon theme to search for icons named after the domains in entry paths.
+    pub theme: Option<String>,
+    /// Icons for every entry in a folder, e.g. `work = "briefcase"`.
+    pub folders: BTreeMap<String, String>,
+}
+
+impl IconsConfig {
+    pub fn enabled(&self) -> bool {
+        self.fields || self.theme.is_some() || !self.folders.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EntryMenuConfig {
+    /// Fields with any of these keys, or words in their keys, are masked until revealed.
+    pub sensitive_keys: Vec<String>,
+}
+
+impl Default for EntryMenuConfig {
+    fn default() -> Self {
+        Self {
+            sensitive_keys: ["pin", "cvv", "secret", "token", "otp_secret", "answer"]
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Keybindings by command name, replacing the defaults. An empty binding
+/// removes the keybinding, leaving the command in the command palette.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeybindingsConfig {
+    pub main_menu: BTreeMap<String, String>,
+    pub entry_menu: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MainMenuConfig {
+    pub order: MainMenuOrder,
+    /// Starts in the folder tree, rather than the list of every entry.
+    pub browse: bool,
+    /// The command run by enter, by its name in `[keybindings.main_menu]`.
+    pub default_action: String,
+    /// Default actions for every entry in a folder, e.g. `otp = "copy_otp"`.
+    pub folders: BTreeMap<String, String>,
+}
+
+impl Default for MainMenuConfig {
+    fn default() -> Self {
+        Self {
+            order: MainMenuOrder::Frecency,
+            browse: false,
+            default_action: "select".to_owned(),
+            folders: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MainMenuOrder {
+    /// The most frequently and recently used entries first, from the usage log.
+    Frecency,
+    Alphabetical,
+}
+
+/// The initial search query of the main menu, see the `prefill` module.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrefillConfig {
+    /// Reads the title and class of the active window when the main menu opens.
+    pub active_window: bool,
+    /// Rules by window class, before the defaults for browsers: `"title"` for
+    /// a domain or site name from the window title, `"class"` for the class
+    /// itself, `""` for no query, or else a fixed query.
+    pub classes: BTreeMap<String, String>,
+}
+
+impl Default for PrefillConfig {
+    fn default() -> Self {
+        Self {
+            active_window: true,
+            classes: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClipboardConfig {
+    /// Seconds until copied data is cleared from the clipboard, or 0 to keep
+    /// it. Defaults to `$PASSWORD_STORE_CLIP_TIME`, or 45.
+    pub clear_after: Option<u64>,
+    /// Clears copied data once it has been pasted.
+    pub paste_once: bool,
+}
+
+/// Where autofill types, see the `autofill` module.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutofillConfig {
+    pub target: AutofillTarget,
+    /// What happens when the target window doesn't match the entry's `url:` field.
+    pub url_mismatch: UrlMismatch,
+    /// Window classes which always need confirmation, such as terminals and chat apps.
+    pub confirm_classes: Vec<String>,
+    /// The autotype sequence of entries without an `autotype:` field.
+    pub sequence: String,
+}
+
+impl Default for AutofillConfig {
+    fn default() -> Self {
+        Self {
+            target: AutofillTarget::Previous,
+            url_mismatch: UrlMismatch::Confirm,
+            confirm_classes: [
+                "xterm",
+                "urxvt",
+                "st-256color",
+                "alacritty",
+                "kitty",
+                "foot",
+                "org.wezfurlong.wezterm",
+                "gnome-terminal-server",
+                "konsole",
+                "xfce4-terminal",
+                "terminator",
+                "tilix",
+                "slack",
+                "discord",
+                "signal",
+                "telegramdesktop",
+                "element",
+                "mattermost",
+                "zoom",
+            ]
+            .iter()
+            .map(|class| class.to_string())
+            .collect(),
+            sequence: "{username} {TAB} {password}".to_owned(),
+        }
+    }
+}
+
+/// The window which autofill types into.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutofillTarget {
+    /// The window which was active before the menus opened, or else a clicked one.
+    #[default]
+    Previous,
+    /// A window clicked after choosing the command.
+    Select,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlMismatch {
+    /// Asks whether to autofill anyway.
+    Confirm,
+    /// Autofills nothing, with an error.
+    Refuse,
+    /// Autofills without checking the window.
+    Allow,
+}
+
+/// The setting of the most specific folder containing an entry.
+pub fn folder_setting<'a>(
+    folders: &'a BTreeMap<String, String>,
+    entry_path: &str,
+) -> Option<&'a str> {
+    folders
+        .iter()
+        .filter(|(folder, _)| {
+            let folder = folder.trim_end_matches('/');
+            entry_path
+                .strip_prefix(folder)
+                .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|(folder, _)| folder.trim_end_matches('/').len())
+        .map(|(_, setting)| setting.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rofi_args_layering() {
+        let config = FileConfig::from_toml(
+            r#"
+            [rofi]
+            theme = "global.rasi"
+            theme_str = "window { width: 50%; }"
+            args = ["-no-fixed-num-lines"]
+
+            [rofi.passphrase]
+            theme = "passphrase.rasi"
+            theme_str = "window { width: 20%; }"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.rofi.args(launcher::Dialog::MainMenu),
+            vec![
+                "-theme",
+                "global.rasi",
+                "-theme-str",
+                "window { width: 50%; }",
+                "-no-fixed-num-lines"
+            ]
+        );
+        assert_eq!(
+            config.rofi.args(launcher::Dialog::Passphrase),
+            vec![
+                "-theme",
+                "passphrase.rasi",
+                "-theme-str",
+                "window { width: 50%; }",
+                "-theme-str",
+                "window { width: 20%; }",
+                "-no-fixed-num-lines"
+            ]
+        );
+    }
+
+    #[test]
+    fn dialog_overrides() {
+        let config = FileConfig::from_toml(
+            r#"
+            [dialogs.editor]
+            prompt = "edit {prompt}"
+
+            [dialogs.main_menu]
+            help = ""
+            "#,
+        )
+        .unwrap();
+
+        let editor = config.dialogs.dialog(launcher::Dialog::Editor);
+        assert_eq!(editor.prompt("username"), "edit username");
+        assert_eq!(editor.help("enter to save"), "enter to save");
+
+        let main_menu = config.dialogs.dialog(launcher::Dialog::MainMenu);
+        assert_eq!(main_menu.prompt("search"), "search");
+        assert_eq!(main_menu.help("enter: select entry"), "");
+    }
+
+    #[test]
+    fn folder_setting_prefers_longest_prefix() {
+        let mut folders = BTreeMap::new();
+        folders.insert("work".to_owned(), "briefcase".to_owned());
+        folders.insert("work/servers/".to_owned(), "server".to_owned());
+
+        assert_eq!(folder_setting(&folders, "work/mail"), Some("briefcase"));
+        assert_eq!(folder_setting(&folders, "work/servers/db"), Some("server"));
+        assert_eq!(folder_setting(&folders, "workshop/mail"), None);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(FileConfig::from_toml("[rofi]\nthem = \"typo.rasi\"").is_err());
+    }
+}
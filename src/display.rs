@@ -0,0 +1,93 @@
+use crate::clipboard;
+use crate::wayland;
+use crate::xorg;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::env;
+use std::fmt;
+use std::process;
+
+/// The clipboard, windows and keyboard of the desktop, through the tools of
+/// its display server.
+pub trait DisplayServer: fmt::Debug {
+    /// The focused window, as an id which the other methods accept.
+    fn get_active_window_id(&self) -> Result<String, Error>;
+
+    /// A window picked by the user, or the focused window if it can't be picked.
+    fn get_window_id_by_user_select(&self) -> Result<String, Error>;
+
+    /// A window's title, or `None` if it can't be read.
+    fn get_window_name(&self, window_id: &str) -> Result<Option<String>, Error>;
+
+    /// The class names of a window, ending with its class or app id.
+    fn get_window_class(&self, window_id: &str) -> Result<Vec<String>, Error>;
+
+    fn type_string_in_window(&self, window_id: &str, typed_string: &str) -> Result<(), Error>;
+
+    /// Types a key, by its xkb keysym name, e.g. `Tab`.
+    fn type_key_in_window(&self, window_id: &str, key: &str) -> Result<(), Error>;
+
+    fn copy_to_clipboard(&self, data: &str) -> Result<(), Error>;
+
+    /// Empties the clipboard, rather than copying an empty text to it, which
+    /// clipboard managers would record.
+    fn clear_clipboard(&self) -> Result<(), Error>;
+
+    /// The text on the clipboard, failing if it is empty or not text.
+    fn get_clipboard(&self) -> Result<String, Error>;
+
+    /// Copies data to the clipboard from a background process, calling `ready`
+    /// once it is there, and then clears it, see the `clipboard` module.
+    fn serve_clipboard(
+        &self,
+        clip: &clipboard::Clip,
+        ready: &mut dyn FnMut(),
+    ) -> Result<(), Error> {
+        clipboard::check_no_queue(clip)?;
+        self.copy_to_clipboard(&clip.copied)?;
+        ready();
+        clipboard::clear_after_timeout(self, clip)
+    }
+
+    /// A screenshot of a region selected by the user, as a png.
+    fn capture_screen_region(&self) -> Result<Vec<u8>, Error>;
+}
+
+/// Wayland if `$WAYLAND_DISPLAY` is set, or else X11.
+pub fn detect() -> Box<dyn DisplayServer> {
+    match env::var("WAYLAND_DISPLAY") {
+        Ok(val) if !val.is_empty() => Box::new(wayland::Wayland::detect()),
+        _ => Box::new(xorg::Xorg),
+    }
+}
+
+/// Runs a program to completion, returning its output.
+pub fn run_command(program: &str, args: &[&str]) -> Result<Vec<u8>, Error> {
+    let output = process::Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to exec {}", program))?;
+
+    check_status(program, output.status)?;
+    Ok(output.stdout)
+}
+
+/// Runs a program to completion, returning its output as text.
+pub fn command_output(program: &str, args: &[&str]) -> Result<String, Error> {
+    String::from_utf8(run_command(program, args)?)
+        .with_context(|| format!("Failed to read {} output as utf8", program))
+}
+
+pub fn check_status(program: &str, status: process::ExitStatus) -> Result<(), Error> {
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to exec {}: exit code {}",
+            program,
+            status
+                .code()
+                .ok_or_else(|| anyhow!("Failed to get exit code of {}", program))?
+        ));
+    };
+    Ok(())
+}
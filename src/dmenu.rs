@@ -0,0 +1,155 @@
+use crate::launcher;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::io::Write;
+use std::process;
+
+/// The dmenu-compatible launchers, which read rows on stdin and print the
+/// selected row (or the typed text) on stdout.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DmenuProgram {
+    Dmenu,
+    Bemenu,
+    Wofi,
+    Fuzzel,
+}
+
+impl DmenuProgram {
+    fn binary(self) -> &'static str {
+        match self {
+            DmenuProgram::Dmenu => "dmenu",
+            DmenuProgram::Bemenu => "bemenu",
+            DmenuProgram::Wofi => "wofi",
+            DmenuProgram::Fuzzel => "fuzzel",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Dmenu {
+    program: DmenuProgram,
+}
+
+impl Dmenu {
+    pub fn new(program: DmenuProgram) -> Self {
+        Self { program }
+    }
+
+    fn command(&self, prompt: &str) -> process::Command {
+        let mut command = process::Command::new(self.program.binary());
+        command
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped());
+
+        match self.program {
+            DmenuProgram::Dmenu => command.arg("-i").args(["-l", "20"]).args(["-p", prompt]),
+            DmenuProgram::Bemenu => command.arg("-i").args(["-l", "20"]).args(["-p", prompt]),
+            DmenuProgram::Wofi => command
+                .arg("--dmenu")
+                .arg("--insensitive")
+                .args(["--prompt", prompt]),
+            DmenuProgram::Fuzzel => command
+                .arg("--dmenu")
+                .args(["--prompt", &format!("{}: ", prompt)]),
+        };
+
+        command
+    }
+
+    /// Runs the launcher, returning `None` if it was cancelled.
+    fn run(&self, mut command: process::Command, rows: &[String]) -> Result<Option<String>, Error> {
+        let binary = self.program.binary();
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to spawn {}", binary))?;
+
+        let stdin = child
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("Failed to open {} stdin", binary))?;
+
+        for row in rows {
+            stdin
+                .write_all(format!("{}\n", row).as_bytes())
+                .with_context(|| format!("Failed to write to {} stdin", binary))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("Failed to read {} stdout", binary))?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let output = String::from_utf8(output.stdout).context("Failed to read output as utf8")?;
+        Ok(Some(output.trim_end_matches('\n').to_owned()))
+    }
+}
+
+impl launcher::Launcher for Dmenu {
+    fn supports_custom_keybindings(&self) -> bool {
+        false
+    }
+
+    fn select(&self, request: &launcher::SelectRequest) -> Result<launcher::Selection, Error> {
+        let output = match self.run(self.command(request.prompt), request.rows)? {
+            Some(val) => val,
+            None => return Ok(Default::default()),
+        };
+
+        // these launchers print the text of the selected row, so rows with
+        // identical text resolve to the first of them
+        Ok(launcher::Selection {
+            indices: request
+                .rows
+                .iter()
+                .position(|row| *row == output)
+                .into_iter()
+                .collect(),
+            command: Some(launcher::SelectedCommand::Select),
+        })
+    }
+
+    fn get_input(
+        &self,
+        _dialog: launcher::Dialog,
+        prompt: &str,
+        initial_value: &str,
+        _message: &str,
+    ) -> Result<Option<String>, Error> {
+        // there is no way to prefill the input, so the old value is offered
+        // as the only row, from where it can be tab completed and edited
+        let rows = match initial_value {
+            "" => Vec::new(),
+            val => vec![val.to_owned()],
+        };
+
+        Ok(self
+            .run(self.command(prompt), &rows)?
+            .map(|val| val.trim().to_owned())
+            .filter(|val| !val.is_empty()))
+    }
+
+    fn get_password(
+        &self,
+        _dialog: launcher::Dialog,
+        prompt: &str,
+        _message: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut command = self.command(prompt);
+        match self.program {
+            // dmenu has no password mode, so draw the input in the background colour
+            DmenuProgram::Dmenu => command.args(["-nb", "#000000", "-nf", "#000000"]),
+            DmenuProgram::Bemenu => command.args(["--password", "indicator"]),
+            DmenuProgram::Wofi => command.arg("--password"),
+            DmenuProgram::Fuzzel => command.arg("--password"),
+        };
+
+        Ok(self
+            .run(command, &[])?
+            .map(|val| val.trim().to_owned())
+            .filter(|val| !val.is_empty()))
+    }
+}
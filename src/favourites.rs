@@ -0,0 +1,98 @@
+//! Favourite entries, which are pinned to the top of the main menu.
+
+use crate::usage;
+use anyhow::Context;
+use anyhow::Error;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, Default)]
+pub struct Favourites {
+    path: PathBuf,
+    pass_store_path: String,
+    /// The favourites of every store, as `(pass_store_path, entry_path)`.
+    entries: BTreeSet<(String, String)>,
+}
+
+impl Favourites {
+    pub fn load(pass_store_path: &str) -> Result<Self, Error> {
+        Self::from_path(&usage::calculate_state_path("favourites")?, pass_store_path)
+    }
+
+    fn from_path(favourites_path: &Path, pass_store_path: &str) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(favourites_path) {
+            Ok(val) => val,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {:?}", favourites_path))
+            }
+        };
+
+        Ok(Self {
+            path: favourites_path.to_owned(),
+            pass_store_path: pass_store_path.trim_end_matches('/').to_owned(),
+            entries: Self::parse(&contents),
+        })
+    }
+
+    fn parse(contents: &str) -> BTreeSet<(String, String)> {
+        contents
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(pass_store_path, entry_path)| {
+                (pass_store_path.to_owned(), entry_path.to_owned())
+            })
+            .collect()
+    }
+
+    fn serialize(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(pass_store_path, entry_path)| format!("{}\t{}\n", pass_store_path, entry_path))
+            .collect()
+    }
+
+    pub fn contains(&self, entry_path: &str) -> bool {
+        self.entries
+            .contains(&(self.pass_store_path.clone(), entry_path.to_owned()))
+    }
+
+    /// Adds or removes a favourite, returning whether it is now a favourite.
+    pub fn toggle(&mut self, entry_path: &str) -> Result<bool, Error> {
+        let key = (self.pass_store_path.clone(), entry_path.to_owned());
+        let favourite = match self.entries.remove(&key) {
+            true => false,
+            false => self.entries.insert(key),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&self.path, self.serialize())
+            .with_context(|| format!("Failed to write {:?}", self.path))?;
+        Ok(favourite)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favourites_are_per_store() {
+        let favourites = Favourites {
+            pass_store_path: "/home/alice/.password-store".to_owned(),
+            entries: Favourites::parse(
+                "/home/alice/.password-store\tgithub.com\n\
+                 /home/alice/work-store\tgitlab.com\n",
+            ),
+            ..Default::default()
+        };
+
+        assert!(favourites.contains("github.com"));
+        assert!(!favourites.contains("gitlab.com"));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::launcher;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::fs;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::process;
+use std::str::FromStr;
+
+/// Runs the menus in the terminal with fzf.
+#[derive(Debug)]
+pub struct Fzf {
+    matching: String,
+}
+
+impl Fzf {
+    pub fn new(matching: &str) -> Self {
+        Self {
+            matching: matching.to_owned(),
+        }
+    }
+
+    fn command(&self, prompt: &str, message: &str) -> process::Command {
+        let mut command = process::Command::new("fzf");
+        command
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .arg("--layout=reverse")
+            .arg(format!("--prompt={}> ", prompt));
+
+        // fzf only knows fuzzy and exact matching
+        if self.matching != "fuzzy" {
+            command.arg("--exact");
+        }
+        if !message.is_empty() {
+            command.arg(format!("--header={}", message));
+        }
+
+        command
+    }
+}
+
+impl launcher::Launcher for Fzf {
+    fn supports_custom_keybindings(&self) -> bool {
+        true
+    }
+
+    fn select(&self, request: &launcher::SelectRequest) -> Result<launcher::Selection, Error> {
+        let keybinds = request
+            .keybinds
+            .iter()
+            .map(|keybind| to_fzf_key(keybind))
+            .collect::<Vec<_>>();
+        // rows are prefixed with their index, which is hidden from the user
+        let mut command = self.command(request.prompt, &request.message_with_help());
+        command.args(["--delimiter", "\t", "--with-nth", "2.."]);
+        if request.multi_select {
+            command.arg("--multi");
+        }
+        if !request.filter.is_empty() {
+            command.arg(format!("--query={}", request.filter));
+        }
+        if !keybinds.is_empty() {
+            command.arg(format!("--expect={}", keybinds.join(",")));
+        }
+
+        let mut child = command.spawn().context("Failed to spawn fzf")?;
+
+        let stdin = child
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("Failed to open fzf stdin"))?;
+
+        for (index, row) in request.rows.iter().enumerate() {
+            stdin
+                .write_all(format!("{}\t{}\n", index, row).as_bytes())
+                .context("Failed to write to fzf stdin")?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .context("Failed to read fzf stdout")?;
+
+        if !output.status.success() {
+            return Ok(Default::default());
+        }
+
+        // with --expect, fzf prints the key that was used before the
+        // selected row, or an empty line if it was enter
+        let output = String::from_utf8(output.stdout).context("Failed to read output as utf8")?;
+        let mut lines = output.lines();
+        let key = match keybinds.is_empty() {
+            true => "",
+            false => lines.next().unwrap_or(""),
+        };
+        let command = match key {
+            "" => launcher::SelectedCommand::Select,
+            key => launcher::SelectedCommand::Custom(
+                keybinds
+                    .iter()
+                    .position(|keybind| keybind == key)
+                    .ok_or_else(|| anyhow!("Unknown fzf key {:?}", key))?,
+            ),
+        };
+        // and then every selected row
+        let indices = lines
+            .filter_map(|line| line.split('\t').next())
+            .map(|val| usize::from_str(val).context("Failed to parse item index as usize"))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(launcher::Selection {
+            indices,
+            command: Some(command),
+        })
+    }
+
+    fn get_input(
+        &self,
+        _dialog: launcher::Dialog,
+        prompt: &str,
+        initial_value: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let output = self
+            .command(prompt, message)
+            .arg("--print-query")
+            .arg(format!("--query={}", initial_value))
+            .spawn()
+            .context("Failed to spawn fzf")?
+            .wait_with_output()
+            .context("Failed to read fzf stdout")?;
+
+        // fzf exits with 1 when the query matches nothing, and 130 when cancelled
+        if output.status.code() == Some(130) {
+            return Ok(None);
+        }
+
+        let output = String::from_utf8(output.stdout).context("Failed to read output as utf8")?;
+        Ok(output
+            .lines()
+            .next()
+            .map(|val| val.trim().to_owned())
+            .filter(|val| !val.is_empty()))
+    }
+
+    fn get_password(
+        &self,
+        _dialog: launcher::Dialog,
+        prompt: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut tty = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .context("Failed to open /dev/tty")?;
+
+        write!(tty, "{}\n{}: ", message, prompt).context("Failed to write to /dev/tty")?;
+        set_tty_echo(&tty, false)?;
+
+        let mut password = String::new();
+        let read = BufReader::new(&tty).read_line(&mut password);
+
+        set_tty_echo(&tty, true)?;
+        writeln!(tty).context("Failed to write to /dev/tty")?;
+        read.context("Failed to read from /dev/tty")?;
+
+        Ok(match password.trim() {
+            "" => None,
+            val => Some(val.to_owned()),
+        })
+    }
+}
+
+fn set_tty_echo(tty: &fs::File, echo: bool) -> Result<(), Error> {
+    let status = process::Command::new("stty")
+        .arg(if echo { "echo" } else { "-echo" })
+        .stdin(tty.try_clone().context("Failed to open /dev/tty")?)
+        .status()
+        .context("Failed to exec stty")?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "Failed to exec stty: exit code {}",
+            status
+                .code()
+                .ok_or_else(|| anyhow!("Failed to get exit code of stty"))?
+        ));
+    };
+    Ok(())
+}
+
+/// Converts a rofi style keybinding (`Control+alt+e`) to fzf's syntax (`ctrl-alt-e`).
+fn to_fzf_key(keybind: &str) -> String {
+    let mut parts = keybind.split('+').collect::<Vec<_>>();
+    let key = parts.pop().unwrap_or("");
+
+    parts
+        .into_iter()
+        .map(|modifier| match modifier.to_lowercase().as_str() {
+            "control" => "ctrl".to_owned(),
+            modifier => modifier.to_owned(),
+        })
+        .chain(std::iter::once(key.to_owned()))
+        .collect::<Vec<_>>()
+        .join("-")
+}
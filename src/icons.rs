@@ -0,0 +1,373 @@
+//! Main menu icons, from an entry's `icon:` field, `[icons.folders]` or the icon theme, cached on disk.
+
+use crate::config;
+use crate::pass;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+const ICON_EXTENSIONS: &[&str] = &["svg", "png", "xpm"];
+
+#[derive(Debug)]
+pub struct Icons<'a> {
+    config: &'a config::IconsConfig,
+    cache: IconCache,
+    theme_index: Option<HashMap<String, (u32, String)>>,
+}
+
+impl<'a> Icons<'a> {
+    pub fn new(config: &'a config::IconsConfig) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            cache: IconCache::from_path(&IconCache::calculate_cache_path()?, &config.theme)?,
+            theme_index: None,
+        })
+    }
+
+    /// Resolves the icon of each entry, or returns an empty list if icons are not configured.
+    pub fn resolve_all(&mut self, entry_paths: &[String]) -> Result<Vec<Option<String>>, Error> {
+        if !self.config.enabled() {
+            return Ok(Vec::new());
+        }
+
+        let icons = entry_paths
+            .iter()
+            .map(|entry_path| self.resolve(entry_path))
+            .collect();
+
+        self.cache.save()?;
+        Ok(icons)
+    }
+
+    fn resolve(&mut self, entry_path: &str) -> Option<String> {
+        if self.config.fields {
+            if let Some(icon) = self.cache.fields.get(entry_path) {
+                return Some(self.resolve_name(&icon.clone()));
+            }
+        }
+
+        if let Some(icon) = config::folder_setting(&self.config.folders, entry_path) {
+            return Some(self.resolve_name(icon));
+        }
+
+        self.config.theme.as_ref()?;
+        domain_candidates(entry_path)
+            .into_iter()
+            .find_map(|name| self.lookup_domain(&name))
+    }
+
+    /// Resolves an icon name from the configuration or an `icon:` field to a
+    /// path in the icon theme, or leaves it to rofi if there is none.
+    fn resolve_name(&mut self, icon: &str) -> String {
+        if icon.starts_with('/') || self.config.theme.is_none() {
+            return icon.to_owned();
+        }
+        self.theme_index()
+            .get(&icon.to_lowercase())
+            .map(|(_, path)| path.to_owned())
+            .unwrap_or_else(|| icon.to_owned())
+    }
+
+    fn lookup_domain(&mut self, name: &str) -> Option<String> {
+        if let Some(cached) = self.cache.domains.get(name) {
+            return cached.clone();
+        }
+
+        let found = self
+            .theme_index()
+            .get(name)
+            .map(|(_, path)| path.to_owned());
+        self.cache.domains.insert(name.to_owned(), found.clone());
+        self.cache.changed = true;
+        found
+    }
+
+    fn theme_index(&mut self) -> &HashMap<String, (u32, String)> {
+        let theme = self.config.theme.clone().unwrap_or_default();
+        self.theme_index
+            .get_or_insert_with(|| index_icon_theme(&theme))
+    }
+}
+
+/// Remembers the `icon:` field of an opened entry for the main menu.
+pub fn remember_entry_icon(
+    config: &config::IconsConfig,
+    entry: &pass::PassEntry,
+) -> Result<(), Error> {
+    if !config.fields {
+        return Ok(());
+    }
+
+    let mut cache = IconCache::from_path(&IconCache::calculate_cache_path()?, &config.theme)?;
+    let icon = entry.get_value_with_key("icon");
+    if cache.fields.get(&entry.path) == icon.as_ref() {
+        return Ok(());
+    }
+
+    match icon {
+        Some(icon) => cache.fields.insert(entry.path.clone(), icon),
+        None => cache.fields.remove(&entry.path),
+    };
+    cache.changed = true;
+    cache.save()
+}
+
+/// The icon names to try for an entry path, from the most to the least
+/// specific, e.g. `web/mail.google.com/alice` gives `mail.google.com`,
+/// `google.com` and `google`.
+fn domain_candidates(entry_path: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+
+    for component in entry_path.split('/') {
+        let domain = component.to_lowercase();
+        let domain = domain.strip_prefix("www.").unwrap_or(&domain);
+        let labels = domain.split('.').collect::<Vec<_>>();
+        let is_domain = labels.len() >= 2
+            && labels.iter().all(|label| !label.is_empty())
+            && labels
+                .last()
+                .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_alphabetic()));
+        if !is_domain {
+            continue;
+        }
+
+        for i in 0..labels.len() - 1 {
+            candidates.push(labels[i..].join("."));
+        }
+        candidates.push(labels[labels.len() - 2].to_owned());
+    }
+
+    candidates.dedup();
+    candidates
+}
+
+/// Maps the lowercase file names of the icons in a theme to the path of their
+/// largest version, preferring scalable icons.
+fn index_icon_theme(theme: &str) -> HashMap<String, (u32, String)> {
+    let mut index = HashMap::new();
+    for theme_dir in icon_theme_dirs(theme) {
+        index_icon_dir(&theme_dir, 0, &mut index);
+    }
+    index
+}
+
+fn index_icon_dir(directory: &Path, size: u32, index: &mut HashMap<String, (u32, String)>) {
+    let entries = match fs::read_dir(directory) {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(val) => val.to_owned(),
+            None => continue,
+        };
+
+        if path.is_dir() {
+            // size directories are named like `48x48`, `48` or `scalable`
+            let size = match name.as_str() {
+                "scalable" => u32::MAX,
+                name => name
+                    .split('x')
+                    .next()
+                    .and_then(|val| val.parse().ok())
+                    .unwrap_or(size),
+            };
+            index_icon_dir(&path, size, index);
+            continue;
+        }
+
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some(val) => val,
+            None => continue,
+        };
+        if !ICON_EXTENSIONS.contains(&extension) {
+            continue;
+        }
+        let path = match path.to_str() {
+            Some(val) => val.to_owned(),
+            None => continue,
+        };
+
+        let stem = stem.to_lowercase();
+        let replace = index
+            .get(&stem)
+            .is_none_or(|(existing_size, _)| size > *existing_size);
+        if replace {
+            index.insert(stem, (size, path));
+        }
+    }
+}
+
+fn icon_theme_dirs(theme: &str) -> Vec<PathBuf> {
+    let mut data_dirs = Vec::new();
+    if let Ok(home) = env::var("HOME") {
+        data_dirs.push(PathBuf::from(format!("{}/.icons", home)));
+    }
+    match (env::var("XDG_DATA_HOME"), env::var("HOME")) {
+        (Ok(val), _) if !val.is_empty() => data_dirs.push(PathBuf::from(val).join("icons")),
+        (_, Ok(val)) => data_dirs.push(PathBuf::from(format!("{}/.local/share/icons", val))),
+        _ => {}
+    }
+    let xdg_data_dirs = match env::var("XDG_DATA_DIRS") {
+        Ok(val) if !val.is_empty() => val,
+        _ => "/usr/local/share:/usr/share".to_owned(),
+    };
+    data_dirs.extend(
+        xdg_data_dirs
+            .split(':')
+            .map(|dir| PathBuf::from(dir).join("icons")),
+    );
+
+    // a theme name may also be a path to the theme's directory
+    if theme.starts_with('/') {
+        return vec![PathBuf::from(theme)];
+    }
+    data_dirs.into_iter().map(|dir| dir.join(theme)).collect()
+}
+
+/// The cache of `icon:` fields and domain lookups.
+///
+/// Each line is `field<TAB>entry path<TAB>icon` or `domain<TAB>name<TAB>path`,
+/// where an empty path records that the icon theme has no such icon. Domain
+/// lookups are dropped when the icon theme changes.
+#[derive(Debug, Default)]
+struct IconCache {
+    path: PathBuf,
+    theme: String,
+    fields: BTreeMap<String, String>,
+    domains: BTreeMap<String, Option<String>>,
+    changed: bool,
+}
+
+impl IconCache {
+    fn calculate_cache_path() -> Result<PathBuf, Error> {
+        match (env::var("XDG_CACHE_HOME"), env::var("HOME")) {
+            (Ok(val), _) if !val.is_empty() => Ok(PathBuf::from(val).join("pass-rofi-gui/icons")),
+            (_, Ok(val)) => Ok(PathBuf::from(format!("{}/.cache/pass-rofi-gui/icons", val))),
+            _ => Err(anyhow!(
+                "Can't find cache directory! Please set $XDG_CACHE_HOME or $HOME"
+            )),
+        }
+    }
+
+    fn from_path(cache_path: &Path, theme: &Option<String>) -> Result<Self, Error> {
+        let mut cache = Self {
+            path: cache_path.to_owned(),
+            theme: theme.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let contents = match fs::read_to_string(cache_path) {
+            Ok(val) => val,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {:?}", cache_path))
+            }
+        };
+        cache.parse(&contents);
+
+        Ok(cache)
+    }
+
+    fn parse(&mut self, contents: &str) {
+        let mut same_theme = false;
+        for line in contents.lines() {
+            let mut parts = line.splitn(3, '\t');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("theme"), Some(theme), None) => same_theme = theme == self.theme,
+                (Some("field"), Some(entry_path), Some(icon)) => {
+                    self.fields.insert(entry_path.to_owned(), icon.to_owned());
+                }
+                (Some("domain"), Some(name), Some(path)) if same_theme => {
+                    let path = Some(path.to_owned()).filter(|path| !path.is_empty());
+                    self.domains.insert(name.to_owned(), path);
+                }
+                _ => self.changed = true,
+            }
+        }
+    }
+
+    fn serialize(&self) -> String {
+        let mut contents = format!("theme\t{}\n", self.theme);
+        for (entry_path, icon) in &self.fields {
+            contents.push_str(&format!("field\t{}\t{}\n", entry_path, icon));
+        }
+        for (name, path) in &self.domains {
+            let path = path.as_deref().unwrap_or("");
+            contents.push_str(&format!("domain\t{}\t{}\n", name, path));
+        }
+        contents
+    }
+
+    fn save(&mut self) -> Result<(), Error> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&self.path, self.serialize())
+            .with_context(|| format!("Failed to write {:?}", self.path))?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domain_candidates_from_path() {
+        assert_eq!(
+            domain_candidates("web/www.mail.google.com/alice"),
+            vec!["mail.google.com", "google.com", "google"]
+        );
+        assert_eq!(
+            domain_candidates("github.com/alice"),
+            vec!["github.com", "github"]
+        );
+        assert!(domain_candidates("bank/pin.v2").is_empty());
+    }
+
+    #[test]
+    fn cache_roundtrip_drops_domains_of_other_themes() {
+        let mut cache = IconCache {
+            theme: "Papirus".to_owned(),
+            ..Default::default()
+        };
+        cache
+            .fields
+            .insert("github.com/alice".to_owned(), "github".to_owned());
+        cache.domains.insert(
+            "github".to_owned(),
+            Some("/usr/share/icons/Papirus/github.svg".to_owned()),
+        );
+        cache.domains.insert("example".to_owned(), None);
+
+        let mut same_theme = IconCache {
+            theme: "Papirus".to_owned(),
+            ..Default::default()
+        };
+        same_theme.parse(&cache.serialize());
+        assert_eq!(same_theme.fields, cache.fields);
+        assert_eq!(same_theme.domains, cache.domains);
+
+        let mut other_theme = IconCache {
+            theme: "hicolor".to_owned(),
+            ..Default::default()
+        };
+        other_theme.parse(&cache.serialize());
+        assert_eq!(other_theme.fields, cache.fields);
+        assert!(other_theme.domains.is_empty());
+    }
+}
@@ -0,0 +1,728 @@
+use crate::config;
+use crate::dmenu;
+use crate::fzf;
+use crate::rofi;
+use anyhow::anyhow;
+use anyhow::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A program which draws the menus and prompts of pass-rofi-gui.
+pub trait Launcher: fmt::Debug {
+    /// Whether `select` can report which of the request's keybinds was used.
+    /// Launchers which can't are shown a second menu of commands instead.
+    fn supports_custom_keybindings(&self) -> bool;
+
+    fn select(&self, request: &SelectRequest) -> Result<Selection, Error>;
+
+    /// Prompts for a line of text, returning `None` on cancel or empty input.
+    fn get_input(
+        &self,
+        dialog: Dialog,
+        prompt: &str,
+        initial_value: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error>;
+
+    /// Prompts for a line of text without displaying it.
+    fn get_password(
+        &self,
+        dialog: Dialog,
+        prompt: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error>;
+
+    fn confirm(&self, prompt: &str, message: &str) -> Result<bool, Error> {
+        let rows = ["no".to_owned(), "yes".to_owned()];
+        let selection = self.select(&SelectRequest {
+            dialog: Dialog::Confirm,
+            prompt,
+            rows: &rows,
+            markup_rows: &[],
+            icons: &[],
+            selected_index: 0,
+            message: "",
+            help: message,
+            keybinds: &[],
+            multi_select: false,
+            filter: "",
+        })?;
+        Ok(selection.index() == Some(1) && selection.command == Some(SelectedCommand::Select))
+    }
+
+    fn show_image(
+        &self,
+        _dialog: Dialog,
+        _prompt: &str,
+        _caption: &str,
+        _image_path: &str,
+    ) -> Result<(), Error> {
+        Err(anyhow!(
+            "Displaying images is not supported by this launcher"
+        ))
+    }
+}
+
+/// The kinds of dialog, which can each be themed and have their prompt and
+/// help text replaced in the configuration file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dialog {
+    MainMenu,
+    EntryMenu,
+    Passphrase,
+    Editor,
+    Confirm,
+}
+
+#[derive(Debug)]
+pub struct SelectRequest<'a> {
+    pub dialog: Dialog,
+    pub prompt: &'a str,
+    pub rows: &'a [String],
+    /// The rows as Pango markup, or empty for launchers to draw the plain rows.
+    pub markup_rows: &'a [String],
+    /// An icon for each row, or empty for no icons.
+    pub icons: &'a [Option<String>],
+    pub selected_index: usize,
+    pub message: &'a str,
+    /// Describes the keybinds, shown below the message.
+    pub help: &'a str,
+    pub keybinds: &'a [&'a str],
+    /// Allows marking several rows, for launchers which support it.
+    pub multi_select: bool,
+    /// The initial search query.
+    pub filter: &'a str,
+}
+
+impl<'a> SelectRequest<'a> {
+    pub fn message_with_help(&self) -> String {
+        match (self.message, self.help) {
+            ("", help) => help.to_owned(),
+            (message, "") => message.to_owned(),
+            (message, help) => format!("{}\n{}", message, help),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Selection {
+    /// The selected rows, of which there can only be several with `multi_select`.
+    pub indices: Vec<usize>,
+    pub command: Option<SelectedCommand>,
+}
+
+impl Selection {
+    pub fn index(&self) -> Option<usize> {
+        self.indices.first().copied()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SelectedCommand {
+    Select,
+    /// The index into the request's keybinds.
+    Custom(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LauncherKind {
+    Rofi,
+    Dmenu,
+    Bemenu,
+    Wofi,
+    Fuzzel,
+    Fzf,
+}
+
+impl LauncherKind {
+    pub const VARIANTS: &'static [&'static str] =
+        &["rofi", "dmenu", "bemenu", "wofi", "fuzzel", "fzf"];
+
+    pub fn build(
+        self,
+        matching: &str,
+        file_config: &config::FileConfig,
+    ) -> Result<Box<dyn Launcher>, Error> {
+        let launcher: Box<dyn Launcher> = match self {
+            LauncherKind::Rofi => Box::new(rofi::Rofi::new(matching, file_config.rofi.clone())?),
+            LauncherKind::Dmenu => Box::new(dmenu::Dmenu::new(dmenu::DmenuProgram::Dmenu)),
+            LauncherKind::Bemenu => Box::new(dmenu::Dmenu::new(dmenu::DmenuProgram::Bemenu)),
+            LauncherKind::Wofi => Box::new(dmenu::Dmenu::new(dmenu::DmenuProgram::Wofi)),
+            LauncherKind::Fuzzel => Box::new(dmenu::Dmenu::new(dmenu::DmenuProgram::Fuzzel)),
+            LauncherKind::Fzf => Box::new(fzf::Fzf::new(matching)),
+        };
+
+        Ok(Box::new(Configured {
+            launcher,
+            dialogs: file_config.dialogs.clone(),
+        }))
+    }
+}
+
+impl FromStr for LauncherKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rofi" => Ok(LauncherKind::Rofi),
+            "dmenu" => Ok(LauncherKind::Dmenu),
+            "bemenu" => Ok(LauncherKind::Bemenu),
+            "wofi" => Ok(LauncherKind::Wofi),
+            "fuzzel" => Ok(LauncherKind::Fuzzel),
+            "fzf" => Ok(LauncherKind::Fzf),
+            _ => Err(anyhow!("Unknown launcher {:?}", s)),
+        }
+    }
+}
+
+/// Applies the prompt and help text overrides of the configuration file to
+/// another launcher.
+#[derive(Debug)]
+pub struct Configured {
+    launcher: Box<dyn Launcher>,
+    dialogs: config::DialogsConfig,
+}
+
+impl Launcher for Configured {
+    fn supports_custom_keybindings(&self) -> bool {
+        self.launcher.supports_custom_keybindings()
+    }
+
+    fn select(&self, request: &SelectRequest) -> Result<Selection, Error> {
+        let dialog = self.dialogs.dialog(request.dialog);
+        self.launcher.select(&SelectRequest {
+            prompt: &dialog.prompt(request.prompt),
+            help: &dialog.help(request.help),
+            ..*request
+        })
+    }
+
+    fn get_input(
+        &self,
+        dialog: Dialog,
+        prompt: &str,
+        initial_value: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let dialog_config = self.dialogs.dialog(dialog);
+        self.launcher.get_input(
+            dialog,
+            &dialog_config.prompt(prompt),
+            initial_value,
+            &dialog_config.help(message),
+        )
+    }
+
+    fn get_password(
+        &self,
+        dialog: Dialog,
+        prompt: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let dialog_config = self.dialogs.dialog(dialog);
+        self.launcher.get_password(
+            dialog,
+            &dialog_config.prompt(prompt),
+            &dialog_config.help(message),
+        )
+    }
+
+    fn confirm(&self, prompt: &str, message: &str) -> Result<bool, Error> {
+        let dialog_config = self.dialogs.dialog(Dialog::Confirm);
+        self.launcher
+            .confirm(&dialog_config.prompt(prompt), &dialog_config.help(message))
+    }
+
+    fn show_image(
+        &self,
+        dialog: Dialog,
+        prompt: &str,
+        caption: &str,
+        image_path: &str,
+    ) -> Result<(), Error> {
+        let dialog_config = self.dialogs.dialog(dialog);
+        self.launcher
+            .show_image(dialog, &dialog_config.prompt(prompt), caption, image_path)
+    }
+}
+
+/// How an item is drawn by launchers which render Pango markup. Defaults to
+/// the escaped `Display` text.
+pub trait Markup: fmt::Display {
+    fn markup(&self) -> String {
+        escape_markup(&self.to_string())
+    }
+}
+
+pub fn escape_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&#39;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// How a menu of items opens, besides the items and keybindings.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SelectOptions<'a> {
+    /// An icon for each item, or empty for no icons.
+    pub icons: &'a [Option<String>],
+    pub selected_index: usize,
+    pub message: Option<&'a str>,
+    /// The initial search query.
+    pub filter: &'a str,
+}
+
+pub fn select_item<TValue: Markup + Clone, TCommand: fmt::Display + Clone>(
+    launcher: &dyn Launcher,
+    dialog: Dialog,
+    items: &[TValue],
+    options: SelectOptions,
+    custom_keybindings: CustomKeybindings<TCommand>,
+) -> Result<SelectedItem<TValue, TCommand>, Error> {
+    SelectedItem::from_items(launcher, dialog, items, options, custom_keybindings)
+}
+
+#[derive(Debug)]
+pub struct SelectedItem<TValue: Markup + Clone, TCommand: fmt::Display + Clone> {
+    pub index: Option<usize>,
+    /// The first selected item.
+    pub value: Option<TValue>,
+    /// Every selected item, starting with `value`. Several items can only be
+    /// selected in the main menu.
+    pub values: Vec<TValue>,
+    pub command: Option<TCommand>,
+    /// Whether the item was chosen with enter, rather than a keybinding or
+    /// the command palette.
+    pub entered: bool,
+}
+
+impl<TValue: Markup + Clone, TCommand: fmt::Display + Clone> SelectedItem<TValue, TCommand> {
+    pub fn from_items(
+        launcher: &dyn Launcher,
+        dialog: Dialog,
+        items: &[TValue],
+        options: SelectOptions,
+        custom_keybindings: CustomKeybindings<TCommand>,
+    ) -> Result<Self, Error> {
+        let supports_custom_keybindings = launcher.supports_custom_keybindings();
+
+        let help = match supports_custom_keybindings {
+            true => custom_keybindings.format_message(),
+            false => String::new(),
+        };
+        let keybinds = match supports_custom_keybindings {
+            true => custom_keybindings.bindings(),
+            false => Vec::new(),
+        };
+        let rows = items
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<_>>();
+        let markup_rows = items.iter().map(|item| item.markup()).collect::<Vec<_>>();
+
+        let selection = launcher.select(&SelectRequest {
+            dialog,
+            prompt: "search",
+            rows: &rows,
+            markup_rows: &markup_rows,
+            icons: options.icons,
+            selected_index: options.selected_index,
+            message: options.message.unwrap_or(""),
+            help: &help,
+            keybinds: &keybinds,
+            // for the bulk commands, which run on every selected entry
+            multi_select: dialog == Dialog::MainMenu,
+            filter: options.filter,
+        })?;
+
+        let values = selection
+            .indices
+            .iter()
+            .map(|item_index| {
+                items.get(*item_index).cloned().ok_or_else(|| {
+                    anyhow!("Failed to index item using index value from launcher output")
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let item = values.first().cloned();
+
+        let mut entered = false;
+        let command = match selection.command {
+            Some(SelectedCommand::Select)
+                if !supports_custom_keybindings
+                    && custom_keybindings.commands().len() > 1
+                    && item.is_some() =>
+            {
+                custom_keybindings.select_command_from_menu(launcher, dialog)?
+            }
+            Some(SelectedCommand::Custom(index))
+                if custom_keybindings.is_palette(index) && item.is_some() =>
+            {
+                custom_keybindings.select_command_from_menu(launcher, dialog)?
+            }
+            Some(command) => {
+                entered = command == SelectedCommand::Select;
+                custom_keybindings.selected_to_command(command)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            index: selection.index(),
+            value: item,
+            values,
+            command,
+            entered,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct CustomKeybindings<TCommand: fmt::Display + Clone> {
+    select_command: TCommand,
+    keybinds: Vec<Keybind<TCommand>>,
+    /// Commands which are only available from the command palette.
+    unbound_commands: Vec<TCommand>,
+    palette_binding: Option<String>,
+}
+
+impl<TCommand: fmt::Display + Clone> CustomKeybindings<TCommand> {
+    pub fn new(select_command: TCommand) -> Self {
+        Self {
+            select_command,
+            keybinds: Vec::new(),
+            unbound_commands: Vec::new(),
+            palette_binding: None,
+        }
+    }
+
+    pub fn add(mut self, keybind: &str, command: TCommand) -> Result<Self, Error> {
+        if self.bindings().len() >= 19 {
+            return Err(anyhow!("Max number of custom rofi keybindings exceeded"));
+        }
+        self.keybinds.push(Keybind {
+            binding: keybind.to_owned(),
+            command,
+        });
+        Ok(self)
+    }
+
+    /// Builds the keybindings from their defaults, with the bindings in
+    /// `overrides` (by command name, or `palette`) replacing the defaults. An
+    /// empty binding leaves the command to the command palette, unless it is
+    /// already the select command.
+    pub fn from_config(
+        select_command: TCommand,
+        defaults: &[(&str, &str, TCommand)],
+        default_palette_binding: &str,
+        overrides: &BTreeMap<String, String>,
+    ) -> Result<Self, Error>
+    where
+        TCommand: PartialEq,
+    {
+        if let Some(name) = overrides.keys().find(|name| {
+            *name != "palette"
+                && !defaults
+                    .iter()
+                    .any(|(default_name, _, _)| default_name == name)
+        }) {
+            return Err(anyhow!("Unknown command {:?} in keybindings", name));
+        }
+        let binding = |name: &str, default: &str| {
+            overrides
+                .get(name)
+                .map(|binding| binding.trim().to_owned())
+                .unwrap_or_else(|| default.to_owned())
+        };
+
+        let bindings = defaults
+            .iter()
+            .map(|(name, default, _)| (*name, binding(name, default)))
+            .chain(std::iter::once((
+                "palette",
+                binding("palette", default_palette_binding),
+            )))
+            .filter(|(_, binding)| !binding.is_empty())
+            .collect::<Vec<_>>();
+        for (i, (name, binding)) in bindings.iter().enumerate() {
+            if let Some((other_name, _)) = bindings[..i].iter().find(|(_, other_binding)| {
+                normalize_binding(other_binding) == normalize_binding(binding)
+            }) {
+                return Err(anyhow!(
+                    "Keybinding {:?} is used by both {:?} and {:?}",
+                    binding,
+                    other_name,
+                    name
+                ));
+            }
+        }
+
+        let mut keybindings = Self::new(select_command);
+        for (name, default, command) in defaults {
+            keybindings = match binding(name, default).as_str() {
+                "" if *command == keybindings.select_command => keybindings,
+                "" => keybindings.add_unbound(command.clone()),
+                binding => keybindings.add(binding, command.clone())?,
+            };
+        }
+        match binding("palette", default_palette_binding).as_str() {
+            "" => Ok(keybindings),
+            binding => keybindings.palette(binding),
+        }
+    }
+
+    /// Adds a command without a keybinding, which is listed in the command palette.
+    pub fn add_unbound(mut self, command: TCommand) -> Self {
+        self.unbound_commands.push(command);
+        self
+    }
+
+    /// Sets the keybinding which opens the command palette, a menu of every command.
+    pub fn palette(mut self, keybind: &str) -> Result<Self, Error> {
+        if self.bindings().len() >= 19 {
+            return Err(anyhow!("Max number of custom rofi keybindings exceeded"));
+        }
+        self.palette_binding = Some(keybind.to_owned());
+        Ok(self)
+    }
+
+    pub fn select_command(&self) -> &TCommand {
+        &self.select_command
+    }
+
+    pub fn keybinds(&self) -> &Vec<Keybind<TCommand>> {
+        &self.keybinds
+    }
+
+    /// Every keybinding in the order they are passed to the launcher, with the
+    /// command palette last.
+    pub fn bindings(&self) -> Vec<&str> {
+        self.keybinds
+            .iter()
+            .map(|keybind| keybind.binding.as_str())
+            .chain(self.palette_binding.as_deref())
+            .collect()
+    }
+
+    /// Every command, in the order they are listed in the command palette.
+    pub fn commands(&self) -> Vec<TCommand> {
+        std::iter::once(&self.select_command)
+            .chain(self.keybinds.iter().map(|keybind| &keybind.command))
+            .chain(self.unbound_commands.iter())
+            .cloned()
+            .collect()
+    }
+
+    pub fn palette_binding(&self) -> Option<&str> {
+        self.palette_binding.as_deref()
+    }
+
+    /// Whether the custom keybinding at `index` of `bindings` opens the command palette.
+    pub fn is_palette(&self, index: usize) -> bool {
+        self.palette_binding.is_some() && index == self.keybinds.len()
+    }
+
+    pub fn format_message(&self) -> String {
+        let mut message = String::new();
+        message.push_str(&format!("enter: {}\n", self.select_command));
+        let descriptions = self
+            .keybinds
+            .iter()
+            .map(|keybind| format!("{}: {}", keybind.binding, keybind.command))
+            .chain(
+                self.palette_binding
+                    .iter()
+                    .map(|binding| format!("{}: all commands", binding)),
+            );
+        for (i, description) in descriptions.enumerate() {
+            message.push_str(&match (i, i % 2 == 0) {
+                (0, true) => format!("{:35}", description),
+                (_, true) => format!("\n{:35}", description),
+                (_, false) => description,
+            });
+        }
+        message
+    }
+
+    pub fn selected_to_command(&self, selected: SelectedCommand) -> Option<TCommand> {
+        match selected {
+            SelectedCommand::Select => Some(self.select_command.clone()),
+            SelectedCommand::Custom(index) => self
+                .keybinds
+                .get(index)
+                .map(|keybind| keybind.command.clone()),
+        }
+    }
+
+    pub fn command_for_binding(&self, binding: &str) -> Option<TCommand> {
+        self.keybinds
+            .iter()
+            .find(|keybind| keybind.binding == binding)
+            .map(|keybind| keybind.command.clone())
+    }
+
+    /// Asks for the command in a second menu: the command palette, which is
+    /// also used by launchers which can't report which keybinding was used.
+    pub fn select_command_from_menu(
+        &self,
+        launcher: &dyn Launcher,
+        dialog: Dialog,
+    ) -> Result<Option<TCommand>, Error> {
+        let commands = self.commands();
+        let rows = commands
+            .iter()
+            .map(|command| command.to_string())
+            .collect::<Vec<_>>();
+
+        let selection = launcher.select(&SelectRequest {
+            dialog,
+            prompt: "command",
+            rows: &rows,
+            markup_rows: &[],
+            icons: &[],
+            selected_index: 0,
+            message: "",
+            help: "",
+            keybinds: &[],
+            multi_select: false,
+            filter: "",
+        })?;
+
+        Ok(match (selection.index(), selection.command) {
+            (Some(index), Some(SelectedCommand::Select)) => commands.get(index).cloned(),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Keybind<TCommand: fmt::Display + Clone> {
+    pub binding: String,
+    pub command: TCommand,
+}
+
+/// Normalizes a rofi style keybinding for comparison: modifiers are case
+/// insensitive and unordered, while the key is case sensitive (`alt+E` is
+/// `alt+shift+e`).
+pub fn normalize_binding(binding: &str) -> String {
+    let mut parts = binding.split('+').map(str::trim).collect::<Vec<_>>();
+    let mut key = parts.pop().unwrap_or("").to_owned();
+
+    let mut modifiers = parts
+        .into_iter()
+        .map(|modifier| match modifier.to_lowercase().as_str() {
+            "ctrl" => "control".to_owned(),
+            "mod1" => "alt".to_owned(),
+            "mod4" => "super".to_owned(),
+            modifier => modifier.to_owned(),
+        })
+        .collect::<Vec<_>>();
+    modifiers.sort();
+    modifiers.dedup();
+
+    if key.len() == 1 && key.chars().all(|c| c.is_ascii_lowercase()) {
+        if let Some(index) = modifiers.iter().position(|modifier| modifier == "shift") {
+            modifiers.remove(index);
+            key = key.to_uppercase();
+        }
+    }
+
+    modifiers
+        .into_iter()
+        .chain(std::iter::once(key))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+pub fn get_passphrase(launcher: &dyn Launcher) -> Result<Option<String>, Error> {
+    launcher.get_password(
+        Dialog::Passphrase,
+        "passphrase",
+        "Please enter the passphrase to unlock the OpenPGP secret key",
+    )
+}
+
+// for passwords; custom bindings to allow for generating password?
+pub fn get_new_field_value(
+    launcher: &dyn Launcher,
+    prompt: &str,
+    old_value: &str,
+) -> Result<Option<String>, Error> {
+    launcher.get_input(
+        Dialog::Editor,
+        prompt,
+        old_value,
+        "enter to save\nctrl+u to clear\nesc or empty input to cancel",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_palette() {
+        let keybindings = CustomKeybindings::new("select")
+            .add("alt+a", "first")
+            .unwrap()
+            .add_unbound("unbound")
+            .palette("alt+x")
+            .unwrap();
+
+        assert_eq!(keybindings.bindings(), vec!["alt+a", "alt+x"]);
+        assert_eq!(keybindings.commands(), vec!["select", "first", "unbound"]);
+        assert!(!keybindings.is_palette(0));
+        assert!(keybindings.is_palette(1));
+        assert_eq!(
+            keybindings.format_message(),
+            format!("enter: select\n{:35}alt+x: all commands", "alt+a: first")
+        );
+    }
+
+    #[test]
+    fn keybindings_from_config() {
+        let defaults = [("first", "alt+a", "first"), ("second", "alt+b", "second")];
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert("first".to_owned(), "".to_owned());
+        overrides.insert("palette".to_owned(), "Alt+a".to_owned());
+        let keybindings =
+            CustomKeybindings::from_config("select", &defaults, "alt+x", &overrides).unwrap();
+        assert_eq!(keybindings.bindings(), vec!["alt+b", "Alt+a"]);
+        assert_eq!(keybindings.commands(), vec!["select", "second", "first"]);
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert("second".to_owned(), "ALT+a".to_owned());
+        assert!(CustomKeybindings::from_config("select", &defaults, "alt+x", &overrides).is_err());
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert("third".to_owned(), "alt+c".to_owned());
+        assert!(CustomKeybindings::from_config("select", &defaults, "alt+x", &overrides).is_err());
+    }
+
+    #[test]
+    fn normalize_bindings() {
+        assert_eq!(normalize_binding("Control+Alt+d"), "alt+control+d");
+        assert_eq!(normalize_binding("alt+ctrl+d"), "alt+control+d");
+        assert_ne!(normalize_binding("alt+E"), normalize_binding("alt+e"));
+        assert_eq!(normalize_binding("alt+E"), normalize_binding("alt+shift+e"));
+        assert_eq!(normalize_binding("Shift+Alt+e"), "alt+E");
+    }
+
+    #[test]
+    fn escape_markup_special_characters() {
+        assert_eq!(
+            escape_markup("<b>Tom & Jerry's \"shop\"</b>"),
+            "&lt;b&gt;Tom &amp; Jerry&#39;s &quot;shop&quot;&lt;/b&gt;"
+        );
+    }
+}
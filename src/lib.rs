@@ -1,14 +1,50 @@
+pub mod autofill;
+pub mod bulk;
 pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod display;
+pub mod dmenu;
+pub mod favourites;
+pub mod fzf;
+pub mod icons;
+pub mod launcher;
 pub mod menu;
+pub mod modi;
 pub mod otp;
 pub mod pass;
+pub mod prefill;
+pub mod qr;
 pub mod rofi;
+pub mod selection;
+pub mod usage;
+pub mod wayland;
 pub mod xorg;
 
 use anyhow::Error;
 
 pub fn run(config: &cli::Config) -> Result<(), Error> {
-    let mut menu = menu::Menu::new();
+    if config.serve_clipboard {
+        return clipboard::run_server(config);
+    }
+    if std::env::var_os("ROFI_RETV").is_some() {
+        return modi::run_script(config);
+    }
+    menu::check_keybindings(config)?;
+    if config.prune_usage_log {
+        let entry_paths = pass::PassStoreDirectory::new(&config.pass_store_path)?.entry_paths;
+        let pruned = usage::UsageLog::load(&config.pass_store_path)?.prune(&entry_paths)?;
+        println!("Removed {} records from the usage log", pruned);
+        return Ok(());
+    }
+    if config.modi {
+        return modi::launch(config);
+    }
+
+    let mut menu = match &config.modi_deferred {
+        Some(deferred) => modi::run_deferred(deferred, config)?,
+        None => menu::Menu::new(config),
+    };
 
     while menu.active() {
         menu = menu.run(config)?;
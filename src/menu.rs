@@ -1,37 +1,70 @@
+use crate::autofill;
+use crate::bulk;
 use crate::cli;
+use crate::clipboard;
+use crate::config;
+use crate::favourites;
+use crate::icons;
+use crate::launcher;
 use crate::otp;
 use crate::pass;
+use crate::prefill;
+use crate::qr;
 use crate::rofi;
-use crate::xorg;
+use crate::usage;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
+use notify_rust::Notification;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::process;
 
 #[derive(Debug, Default)]
 pub struct Menu {
     state: MenuState,
+    main_menu_level: MainMenuLevel,
     main_menu_selected_index: usize,
     entry_menu_selected_index: usize,
+    entry_menu_revealed_index: Option<usize>,
+    /// The initial search query, which is only used the first time the main
+    /// menu is shown.
+    main_menu_filter: String,
 }
 
-#[derive(Debug, PartialEq)]
-enum MenuState {
+#[derive(Debug, PartialEq, Default)]
+pub enum MenuState {
+    #[default]
     MainMenu,
     EntryMenu(pass::PassEntry),
     Done,
 }
 
-impl Default for MenuState {
-    fn default() -> Self {
-        MenuState::MainMenu
+impl Menu {
+    pub fn new(config: &cli::Config) -> Self {
+        Self {
+            main_menu_level: MainMenuLevel::new(config),
+            main_menu_filter: prefill::initial_query(config),
+            ..Default::default()
+        }
     }
-}
 
-impl Menu {
-    pub fn new() -> Self {
-        Default::default()
+    pub fn with_state(
+        state: MenuState,
+        main_menu_level: MainMenuLevel,
+        main_menu_selected_index: usize,
+        entry_menu_selected_index: usize,
+    ) -> Self {
+        Self {
+            state,
+            main_menu_level,
+            main_menu_selected_index,
+            entry_menu_selected_index,
+            entry_menu_revealed_index: None,
+            main_menu_filter: String::new(),
+        }
     }
 
     pub fn active(&self) -> bool {
@@ -43,21 +76,173 @@ impl Menu {
             state: match self.state {
                 MenuState::MainMenu => {
                     self.entry_menu_selected_index = 0;
-                    main_menu(&mut self.main_menu_selected_index, config)?
+                    self.entry_menu_revealed_index = None;
+                    main_menu(
+                        &mut self.main_menu_level,
+                        &mut self.main_menu_selected_index,
+                        &std::mem::take(&mut self.main_menu_filter),
+                        config,
+                    )?
                 }
                 MenuState::EntryMenu(entry) => {
-                    entry_menu(&mut self.entry_menu_selected_index, entry, config)?
+                    let entry_path = entry.path.clone();
+                    let state = entry_menu(
+                        &mut self.entry_menu_selected_index,
+                        &mut self.entry_menu_revealed_index,
+                        entry,
+                        config,
+                    )?;
+                    // the entry may have moved since it was selected
+                    if state == MenuState::MainMenu {
+                        self.main_menu_selected_index =
+                            main_menu_index(config, &self.main_menu_level, &entry_path)?;
+                    }
+                    state
                 }
                 MenuState::Done => self.state,
             },
+            main_menu_level: self.main_menu_level,
             main_menu_selected_index: self.main_menu_selected_index,
             entry_menu_selected_index: self.entry_menu_selected_index,
+            entry_menu_revealed_index: self.entry_menu_revealed_index,
+            main_menu_filter: self.main_menu_filter,
         })
     }
 }
 
-#[derive(Debug, Clone)]
+/// A row of the main menu: an entry, drawn with the folders dimmed and the
+/// name in bold, after a star if it is a favourite, or a folder when browsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MainMenuEntry {
+    pub path: String,
+    /// The path relative to the folder being browsed.
+    pub label: String,
+    pub favourite: bool,
+    /// The number of entries in the folder, if this is a folder.
+    pub folder_size: Option<usize>,
+}
+
+const FAVOURITE_MARK: &str = "★ ";
+
+impl fmt::Display for MainMenuEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.folder_size, self.favourite) {
+            (Some(size), _) => write!(f, "{}/ ({})", self.label, size),
+            (None, true) => write!(f, "{}{}", FAVOURITE_MARK, self.label),
+            (None, false) => write!(f, "{}", self.label),
+        }
+    }
+}
+
+impl launcher::Markup for MainMenuEntry {
+    fn markup(&self) -> String {
+        if let Some(size) = self.folder_size {
+            return format!(
+                "<b>{}/</b> <span alpha=\"50%\">({})</span>",
+                launcher::escape_markup(&self.label),
+                size
+            );
+        }
+        let mark = match self.favourite {
+            true => FAVOURITE_MARK,
+            false => "",
+        };
+        match self.label.rsplit_once('/') {
+            Some((folder, name)) => format!(
+                "{}<span alpha=\"50%\">{}/</span><b>{}</b>",
+                mark,
+                launcher::escape_markup(folder),
+                launcher::escape_markup(name)
+            ),
+            None => format!("{}<b>{}</b>", mark, launcher::escape_markup(&self.label)),
+        }
+    }
+}
+
+const MASK: &str = "••••••";
+
+/// A field in the entry menu, drawn with its key dimmed and, if it is
+/// sensitive, its value masked.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryField<'a> {
+    pub field: &'a pass::PassEntryField,
+    pub masked: bool,
+}
+
+impl<'a> EntryField<'a> {
+    /// The fields of an entry, with the sensitive ones masked unless revealed.
+    pub fn from_entry(
+        entry: &'a pass::PassEntry,
+        revealed_index: Option<usize>,
+        config: &cli::Config,
+    ) -> Vec<Self> {
+        entry
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| Self {
+                field,
+                masked: revealed_index != Some(index)
+                    && is_sensitive(field, &config.file_config.entry_menu.sensitive_keys),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for EntryField<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.field, self.masked) {
+            (pass::PassEntryField::KeyVal(key, _), true) => write!(f, "{}: {}", key, MASK),
+            (_, true) => write!(f, "{}", MASK),
+            (field, false) => write!(f, "{}", field),
+        }
+    }
+}
+
+impl launcher::Markup for EntryField<'_> {
+    fn markup(&self) -> String {
+        match self.field {
+            pass::PassEntryField::KeyVal(key, val) => format!(
+                "<span alpha=\"50%\">{}:</span> {}",
+                launcher::escape_markup(key),
+                match self.masked {
+                    true => MASK.to_owned(),
+                    false => launcher::escape_markup(val),
+                }
+            ),
+            _ => launcher::escape_markup(&self.to_string()),
+        }
+    }
+}
+
+/// Whether a field should be masked: the password, otpauth uris and fields
+/// whose key, or a word in it, is one of the sensitive keys.
+pub fn is_sensitive(field: &pass::PassEntryField, sensitive_keys: &[String]) -> bool {
+    match field {
+        pass::PassEntryField::Password(_) => true,
+        pass::PassEntryField::Other(val) => val.starts_with("otpauth://"),
+        pass::PassEntryField::KeyVal(key, val) => {
+            let key = key.to_lowercase();
+            val.starts_with("otpauth://")
+                || sensitive_keys.iter().any(|sensitive_key| {
+                    let sensitive_key = sensitive_key.to_lowercase();
+                    key == sensitive_key
+                        || key
+                            .split(|c: char| !c.is_alphanumeric())
+                            .any(|word| word == sensitive_key)
+                })
+        }
+    }
+}
+
+impl launcher::Markup for qr::QrImageSource {}
+
+impl launcher::Markup for otp::OtpAccount {}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MainMenuCommand {
+    /// Enter, when its command depends on the folder of the entry.
+    Default,
     Select,
     AutofillEmail,
     AutofillUsername,
@@ -68,157 +253,588 @@ pub enum MainMenuCommand {
     CopyUsername,
     CopyPassword,
     CopyOTP,
+    CopyLogin,
     OpenURLInBrowser,
+    ToggleFavourite,
+    Browse,
+    ParentFolder,
+    MoveEntries,
+    DeleteEntries,
+    ReencryptEntries,
+    ExportEntries,
+}
+
+impl MainMenuCommand {
+    /// Whether the command runs on every selected entry, see the `bulk` module.
+    pub fn is_bulk(&self) -> bool {
+        matches!(
+            self,
+            MainMenuCommand::MoveEntries
+                | MainMenuCommand::DeleteEntries
+                | MainMenuCommand::ReencryptEntries
+                | MainMenuCommand::ExportEntries
+        )
+    }
 }
 
 impl fmt::Display for MainMenuCommand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            MainMenuCommand::Default => write!(f, "default action"),
             MainMenuCommand::Select => write!(f, "select entry"),
             MainMenuCommand::AutofillEmail => write!(f, "autofill email"),
             MainMenuCommand::AutofillUsername => write!(f, "autofill username"),
             MainMenuCommand::AutofillPassword => write!(f, "autofill password"),
             MainMenuCommand::AutofillOTP => write!(f, "autofill otp"),
-            MainMenuCommand::AutofillCustom => write!(f, "autofill (user/email)+pass"),
+            MainMenuCommand::AutofillCustom => write!(f, "autotype"),
             MainMenuCommand::CopyEmail => write!(f, "copy email"),
             MainMenuCommand::CopyUsername => write!(f, "copy username"),
             MainMenuCommand::CopyPassword => write!(f, "copy password"),
             MainMenuCommand::CopyOTP => write!(f, "copy otp"),
+            MainMenuCommand::CopyLogin => write!(f, "copy login to paste in turn"),
             MainMenuCommand::OpenURLInBrowser => write!(f, "open url in web browser"),
+            MainMenuCommand::ToggleFavourite => write!(f, "pin/unpin entry"),
+            MainMenuCommand::Browse => write!(f, "browse folders/search all"),
+            MainMenuCommand::ParentFolder => write!(f, "parent folder"),
+            MainMenuCommand::MoveEntries => write!(f, "move selected entries"),
+            MainMenuCommand::DeleteEntries => write!(f, "delete selected entries"),
+            MainMenuCommand::ReencryptEntries => write!(f, "re-encrypt selected entries"),
+            MainMenuCommand::ExportEntries => write!(f, "export selected entries"),
         }
     }
 }
 
+/// The main menu commands by their name in the configuration file, with their default keybindings.
+const MAIN_MENU_KEYBINDINGS: &[(&str, &str, MainMenuCommand)] = &[
+    ("select", "", MainMenuCommand::Select),
+    ("autofill_email", "alt+e", MainMenuCommand::AutofillEmail),
+    ("copy_email", "alt+E", MainMenuCommand::CopyEmail),
+    (
+        "autofill_username",
+        "alt+u",
+        MainMenuCommand::AutofillUsername,
+    ),
+    ("copy_username", "alt+U", MainMenuCommand::CopyUsername),
+    (
+        "autofill_password",
+        "alt+p",
+        MainMenuCommand::AutofillPassword,
+    ),
+    ("copy_password", "alt+P", MainMenuCommand::CopyPassword),
+    ("autofill_otp", "alt+o", MainMenuCommand::AutofillOTP),
+    ("copy_otp", "alt+O", MainMenuCommand::CopyOTP),
+    ("copy_login", "", MainMenuCommand::CopyLogin),
+    ("autofill_custom", "alt+a", MainMenuCommand::AutofillCustom),
+    ("open_url", "alt+w", MainMenuCommand::OpenURLInBrowser),
+    ("favourite", "alt+s", MainMenuCommand::ToggleFavourite),
+    ("browse", "alt+n", MainMenuCommand::Browse),
+    ("parent_folder", "alt+Left", MainMenuCommand::ParentFolder),
+    // only in the command palette by default, as rofi script mode is out of keybindings
+    ("move", "", MainMenuCommand::MoveEntries),
+    ("delete", "", MainMenuCommand::DeleteEntries),
+    ("reencrypt", "", MainMenuCommand::ReencryptEntries),
+    ("export", "", MainMenuCommand::ExportEntries),
+];
+
+const PALETTE_KEYBINDING: &str = "alt+x";
+
+pub fn main_menu_keybindings(
+    file_config: &config::FileConfig,
+) -> Result<launcher::CustomKeybindings<MainMenuCommand>, Error> {
+    let main_menu_config = &file_config.main_menu;
+    for action in main_menu_config.folders.values() {
+        main_menu_command_by_name(action)?;
+    }
+    // with folder defaults, enter's command is only known once an entry is selected
+    let select_command = match main_menu_config.folders.is_empty() {
+        true => main_menu_command_by_name(&main_menu_config.default_action)?,
+        false => MainMenuCommand::Default,
+    };
+
+    launcher::CustomKeybindings::from_config(
+        select_command,
+        MAIN_MENU_KEYBINDINGS,
+        PALETTE_KEYBINDING,
+        &file_config.keybindings.main_menu,
+    )
+    .context("Failed to read main menu keybindings")
+}
+
+fn main_menu_command_name(command: &MainMenuCommand) -> &'static str {
+    MAIN_MENU_KEYBINDINGS
+        .iter()
+        .find(|(_, _, other_command)| other_command == command)
+        .map_or("default", |(name, _, _)| name)
+}
+
+fn main_menu_command_by_name(name: &str) -> Result<MainMenuCommand, Error> {
+    MAIN_MENU_KEYBINDINGS
+        .iter()
+        .find(|(command_name, _, _)| *command_name == name)
+        .map(|(_, _, command)| command.clone())
+        .ok_or_else(|| anyhow!("Unknown main menu action {:?}", name))
+}
+
+/// The command run by enter on an entry: the default action of its folder,
+/// or else the global one.
+pub fn default_action(
+    main_menu_config: &config::MainMenuConfig,
+    entry_path: &str,
+) -> Result<MainMenuCommand, Error> {
+    main_menu_command_by_name(
+        config::folder_setting(&main_menu_config.folders, entry_path)
+            .unwrap_or(&main_menu_config.default_action),
+    )
+}
+
+/// The entries of the main menu: the favourites, and then the rest, each in
+/// the configured order.
+pub fn main_menu_entries(config: &cli::Config) -> Result<Vec<MainMenuEntry>, Error> {
+    let mut entry_paths = pass::PassStoreDirectory::new(&config.pass_store_path)?.entry_paths;
+    if config.usage_log && config.file_config.main_menu.order == config::MainMenuOrder::Frecency {
+        usage::UsageLog::load(&config.pass_store_path)?.sort_by_frecency(&mut entry_paths)?;
+    }
+
+    let favourites = favourites::Favourites::load(&config.pass_store_path)?;
+    let mut entries = entry_paths
+        .into_iter()
+        .map(|path| MainMenuEntry {
+            label: path.clone(),
+            favourite: favourites.contains(&path),
+            folder_size: None,
+            path,
+        })
+        .collect::<Vec<_>>();
+    // a stable sort, which keeps the order within favourites and the rest
+    entries.sort_by_key(|entry| !entry.favourite);
+    Ok(entries)
+}
+
+/// The rows of a level of the main menu: every entry, or the subfolders of
+/// the folder being browsed followed by its entries.
+pub fn main_menu_rows(
+    config: &cli::Config,
+    level: &MainMenuLevel,
+) -> Result<Vec<MainMenuEntry>, Error> {
+    let entries = main_menu_entries(config)?;
+    Ok(match &level.folder {
+        Some(folder) => folder_rows(entries, folder),
+        None => entries,
+    })
+}
+
+/// The subfolders of a folder, with their number of entries, followed by the
+/// entries in the folder.
+fn folder_rows(entries: Vec<MainMenuEntry>, folder: &str) -> Vec<MainMenuEntry> {
+    let prefix = match folder.is_empty() {
+        true => String::new(),
+        false => format!("{}/", folder),
+    };
+
+    let mut folder_sizes = BTreeMap::new();
+    let mut folder_entries = Vec::new();
+    for entry in entries {
+        let label = match entry.path.strip_prefix(&prefix) {
+            Some(label) => label.to_owned(),
+            None => continue,
+        };
+        match label.split_once('/') {
+            Some((name, _)) => *folder_sizes.entry(name.to_owned()).or_insert(0) += 1,
+            None => folder_entries.push(MainMenuEntry { label, ..entry }),
+        }
+    }
+
+    folder_sizes
+        .into_iter()
+        .map(|(name, size)| MainMenuEntry {
+            path: format!("{}{}", prefix, name),
+            label: name,
+            favourite: false,
+            folder_size: Some(size),
+        })
+        .chain(folder_entries)
+        .collect()
+}
+
+/// The entry paths of selected rows, with every entry in selected folders.
+pub fn selected_entry_paths(
+    config: &cli::Config,
+    rows: &[MainMenuEntry],
+) -> Result<Vec<String>, Error> {
+    let mut all_entry_paths = None;
+    let mut entry_paths: Vec<String> = Vec::new();
+    for row in rows {
+        let row_entry_paths = match row.folder_size {
+            Some(_) => {
+                let prefix = format!("{}/", row.path);
+                let all_entry_paths = match &mut all_entry_paths {
+                    Some(val) => val,
+                    None => all_entry_paths.insert(
+                        pass::PassStoreDirectory::new(&config.pass_store_path)?.entry_paths,
+                    ),
+                };
+                all_entry_paths
+                    .iter()
+                    .filter(|entry_path| entry_path.starts_with(&prefix))
+                    .cloned()
+                    .collect()
+            }
+            None => vec![row.path.clone()],
+        };
+        for entry_path in row_entry_paths {
+            if !entry_paths.contains(&entry_path) {
+                entry_paths.push(entry_path);
+            }
+        }
+    }
+    Ok(entry_paths)
+}
+
+/// The icons of the rows of the main menu, with a generic icon for folders.
+pub fn main_menu_icons(
+    config: &cli::Config,
+    rows: &[MainMenuEntry],
+) -> Result<Vec<Option<String>>, Error> {
+    let mut icons = icons::Icons::new(&config.file_config.icons)?
+        .resolve_all(&rows.iter().map(|row| row.path.clone()).collect::<Vec<_>>())?;
+    for (icon, row) in icons.iter_mut().zip(rows) {
+        if row.folder_size.is_some() && icon.is_none() {
+            *icon = Some("folder".to_owned());
+        }
+    }
+    Ok(icons)
+}
+
+/// The index of a row in a level of the main menu, which changes as entries
+/// are used and pinned.
+pub fn main_menu_index(
+    config: &cli::Config,
+    level: &MainMenuLevel,
+    path: &str,
+) -> Result<usize, Error> {
+    Ok(main_menu_rows(config, level)?
+        .iter()
+        .position(|row| row.path == path)
+        .unwrap_or_default())
+}
+
+/// Where the main menu is in the folder tree, when browsing folders.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MainMenuLevel {
+    /// The folder being browsed (empty for the root of the store), or `None`
+    /// for the flat list of every entry.
+    pub folder: Option<String>,
+    /// The selected index of every other level visited, by `level_key`.
+    selected_indices: BTreeMap<String, usize>,
+}
+
+impl MainMenuLevel {
+    pub fn new(config: &cli::Config) -> Self {
+        Self {
+            folder: Some(String::new()).filter(|_| config.file_config.main_menu.browse),
+            ..Default::default()
+        }
+    }
+
+    /// The title of the level, shown above the rows.
+    pub fn message(&self) -> Option<String> {
+        self.folder.as_ref().map(|folder| format!("{}/", folder))
+    }
+
+    fn level_key(folder: &Option<String>) -> String {
+        match folder {
+            Some(folder) => format!("/{}", folder),
+            None => String::new(),
+        }
+    }
+
+    /// Moves to another level, restoring its selected index, or selecting
+    /// the row at `path` if it is in the level.
+    fn go_to(
+        &mut self,
+        folder: Option<String>,
+        selected_index: &mut usize,
+        path: &str,
+        config: &cli::Config,
+    ) -> Result<(), Error> {
+        self.selected_indices
+            .insert(Self::level_key(&self.folder), *selected_index);
+        self.folder = folder;
+        let rows = main_menu_rows(config, self)?;
+        *selected_index = match rows.iter().position(|row| row.path == path) {
+            Some(index) => index,
+            None => self
+                .selected_indices
+                .get(&Self::level_key(&self.folder))
+                .copied()
+                .filter(|index| *index < rows.len())
+                .unwrap_or_default(),
+        };
+        Ok(())
+    }
+
+    /// Moves up from folders which no longer have any entries.
+    pub fn leave_empty_folders(
+        &mut self,
+        selected_index: &mut usize,
+        config: &cli::Config,
+    ) -> Result<(), Error> {
+        while let Some(folder) = self.folder.clone().filter(|folder| !folder.is_empty()) {
+            if !main_menu_rows(config, self)?.is_empty() {
+                break;
+            }
+            let parent = parent_folder(&folder).to_owned();
+            self.go_to(Some(parent), selected_index, &folder, config)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the commands which move between levels, returning whether the
+    /// command was handled. Folders are entered with enter, whichever command
+    /// it is bound to, and have no other commands.
+    pub fn navigate(
+        &mut self,
+        selected_index: &mut usize,
+        row: &MainMenuEntry,
+        command: &MainMenuCommand,
+        entered: bool,
+        config: &cli::Config,
+    ) -> Result<bool, Error> {
+        match (command, &self.folder) {
+            (MainMenuCommand::Browse, Some(_)) => {
+                self.go_to(None, selected_index, &row.path, config)?
+            }
+            (MainMenuCommand::Browse, None) => {
+                let folder = parent_folder(&row.path).to_owned();
+                self.go_to(Some(folder), selected_index, &row.path, config)?
+            }
+            (MainMenuCommand::ParentFolder, Some(folder)) if !folder.is_empty() => {
+                let folder = folder.clone();
+                let parent = parent_folder(&folder).to_owned();
+                self.go_to(Some(parent), selected_index, &folder, config)?
+            }
+            (MainMenuCommand::ParentFolder, _) => (),
+            // the palette's select and default also open folders
+            _ if row.folder_size.is_some()
+                && (entered
+                    || matches!(command, MainMenuCommand::Default | MainMenuCommand::Select)) =>
+            {
+                self.go_to(Some(row.path.clone()), selected_index, "", config)?
+            }
+            _ if row.folder_size.is_some() && !command.is_bulk() => (),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn parent_folder(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// Pins or unpins an entry to the top of the main menu.
+pub fn toggle_favourite(config: &cli::Config, entry_path: &str) -> Result<(), Error> {
+    favourites::Favourites::load(&config.pass_store_path)?.toggle(entry_path)?;
+    Ok(())
+}
+
+/// Records a main menu command in the usage log, unless it is disabled.
+pub fn record_usage(
+    config: &cli::Config,
+    entry_path: &str,
+    command: &MainMenuCommand,
+) -> Result<(), Error> {
+    if !config.usage_log {
+        return Ok(());
+    }
+    usage::UsageLog::load(&config.pass_store_path)?
+        .record(entry_path, main_menu_command_name(command))
+}
+
 fn main_menu(
+    main_menu_level: &mut MainMenuLevel,
     main_menu_selected_index: &mut usize,
+    filter: &str,
     config: &cli::Config,
 ) -> Result<MenuState, Error> {
-    let pass_store_dir = pass::PassStoreDirectory::new(&config.pass_store_path)?;
-
-    let keybinds = rofi::RofiCustomKeybindings::new(MainMenuCommand::Select)
-        .add("alt+e", MainMenuCommand::AutofillEmail)?
-        .add("alt+E", MainMenuCommand::CopyEmail)?
-        .add("alt+u", MainMenuCommand::AutofillUsername)?
-        .add("alt+U", MainMenuCommand::CopyUsername)?
-        .add("alt+p", MainMenuCommand::AutofillPassword)?
-        .add("alt+P", MainMenuCommand::CopyPassword)?
-        .add("alt+o", MainMenuCommand::AutofillOTP)?
-        .add("alt+O", MainMenuCommand::CopyOTP)?
-        .add("alt+a", MainMenuCommand::AutofillCustom)?
-        .add("alt+w", MainMenuCommand::OpenURLInBrowser)?;
-
-    let selected = rofi::select_item(
-        &pass_store_dir.entry_paths,
-        &config.rofi_matching,
-        *main_menu_selected_index,
+    main_menu_level.leave_empty_folders(main_menu_selected_index, config)?;
+    let rows = main_menu_rows(config, main_menu_level)?;
+
+    let keybinds = main_menu_keybindings(&config.file_config)?;
+    let icons = main_menu_icons(config, &rows)?;
+
+    let selected = launcher::select_item(
+        &*config.launcher,
+        launcher::Dialog::MainMenu,
+        &rows,
+        launcher::SelectOptions {
+            icons: &icons,
+            selected_index: *main_menu_selected_index,
+            message: main_menu_level.message().as_deref(),
+            filter,
+        },
         keybinds,
     )?;
 
     *main_menu_selected_index = selected.index.unwrap_or_default();
-    let entry_path = selected.value.ok_or_else(|| anyhow!("No entry selected"))?;
+    let row = selected.value.ok_or_else(|| anyhow!("No entry selected"))?;
     let command = selected
         .command
-        .ok_or_else(|| anyhow!("Rofi command code not found"))?;
+        .ok_or_else(|| anyhow!("No command selected"))?;
+    if command.is_bulk() {
+        let entry_paths = selected_entry_paths(config, &selected.values)?;
+        bulk::run_bulk_command(&command, &entry_paths, config)?;
+        return Ok(MenuState::MainMenu);
+    }
+    if main_menu_level.navigate(
+        main_menu_selected_index,
+        &row,
+        &command,
+        selected.entered,
+        config,
+    )? {
+        return Ok(MenuState::MainMenu);
+    }
+    if selected.values.len() > 1 {
+        return Err(anyhow!(
+            "Refused to run {} on {} marked rows, as it runs on a single entry",
+            main_menu_command_name(&command),
+            selected.values.len()
+        ));
+    }
 
-    let entry = pass::PassEntry::from_path(&entry_path)?;
+    let entry_path = row.path;
+    let command = match command {
+        MainMenuCommand::Default => default_action(&config.file_config.main_menu, &entry_path)?,
+        command => command,
+    };
+    if command == MainMenuCommand::ToggleFavourite {
+        toggle_favourite(config, &entry_path)?;
+        *main_menu_selected_index = main_menu_index(config, main_menu_level, &entry_path)?;
+        return Ok(MenuState::MainMenu);
+    }
+    record_usage(config, &entry_path, &command)?;
 
+    let entry = pass::PassEntry::from_path(&entry_path, &*config.launcher)?;
+    icons::remember_entry_icon(&config.file_config.icons, &entry)?;
+
+    run_main_menu_command(entry, command, config)
+}
+
+pub fn run_main_menu_command(
+    entry: pass::PassEntry,
+    command: MainMenuCommand,
+    config: &cli::Config,
+) -> Result<MenuState, Error> {
     match command {
+        MainMenuCommand::Default => {
+            let command = default_action(&config.file_config.main_menu, &entry.path)?;
+            return run_main_menu_command(entry, command, config);
+        }
+
         MainMenuCommand::Select => {
             return Ok(MenuState::EntryMenu(entry));
         }
 
-        MainMenuCommand::AutofillEmail => xorg::type_string_in_window(
-            &xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?,
-            &entry
-                .get_value_with_key("email")
-                .ok_or_else(|| anyhow!("No email found in entry"))?,
-        )
-        .context("Failed to focus window by window_id")?,
+        MainMenuCommand::AutofillEmail => autofill::autofill(
+            &entry,
+            &[autofill::AutofillStep::Text(
+                entry
+                    .get_value_with_key("email")
+                    .ok_or_else(|| anyhow!("No email found in entry"))?,
+            )],
+            config,
+        )?,
 
-        MainMenuCommand::AutofillUsername => xorg::type_string_in_window(
-            &xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?,
-            &entry
-                .get_value_with_key("username")
-                .ok_or_else(|| anyhow!("No username found in entry"))?,
-        )
-        .context("Failed to focus window by window_id")?,
+        MainMenuCommand::AutofillUsername => autofill::autofill(
+            &entry,
+            &[autofill::AutofillStep::Text(
+                entry
+                    .get_value_with_key("username")
+                    .ok_or_else(|| anyhow!("No username found in entry"))?,
+            )],
+            config,
+        )?,
 
-        MainMenuCommand::AutofillPassword => xorg::type_string_in_window(
-            &xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?,
-            &entry
-                .get_password()
-                .ok_or_else(|| anyhow!("No password found in entry"))?,
-        )
-        .context("Failed to focus window by window_id")?,
+        MainMenuCommand::AutofillPassword => autofill::autofill(
+            &entry,
+            &[autofill::AutofillStep::Text(
+                entry
+                    .get_password()
+                    .ok_or_else(|| anyhow!("No password found in entry"))?,
+            )],
+            config,
+        )?,
 
-        MainMenuCommand::AutofillOTP => xorg::type_string_in_window(
-            &xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?,
-            &otp::calculate_otp(
-                &entry
-                    .get_value_with_key("otp_secret")
-                    .ok_or_else(|| anyhow!("No otp_secret found in entry"))?,
-            )
-            .context("Failed to calculate otp from secret")?,
-        )
-        .context("Failed to focus window by window_id")?,
+        MainMenuCommand::AutofillOTP => {
+            autofill::autofill(&entry, &[autofill::AutofillStep::otp(&entry)?], config)?
+        }
 
         MainMenuCommand::AutofillCustom => {
-            let window_id = xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?;
-            let username_or_email = entry
-                .get_value_with_key("username")
-                .or_else(|| entry.get_value_with_key("email"))
-                .ok_or_else(|| anyhow!("No username nor email found in entry"))?;
-            let password = entry
-                .get_password()
-                .ok_or_else(|| anyhow!("No password found in entry"))?;
+            let sequence = entry
+                .get_value_with_key("autotype")
+                .unwrap_or_else(|| config.file_config.autofill.sequence.clone());
+            let steps = autofill::parse_sequence(&sequence, &entry)
+                .with_context(|| format!("Failed to parse autotype sequence {:?}", sequence))?;
 
-            xorg::type_string_in_window(&window_id, &username_or_email)
-                .context("Failed to type username or email in window")?;
-            xorg::type_key_in_window(&window_id, "Tab")
-                .context("Failed to type tab key in window")?;
-            xorg::type_string_in_window(&window_id, &password)
-                .context("Failed to type password in window")?;
+            autofill::autofill(&entry, &steps, config)?;
         }
 
-        MainMenuCommand::CopyEmail => xorg::copy_to_clipboard(
+        MainMenuCommand::CopyEmail => clipboard::copy(
             &entry
                 .get_value_with_key("email")
                 .ok_or_else(|| anyhow!("No email found in entry"))?,
+            config,
         )?,
 
-        MainMenuCommand::CopyUsername => xorg::copy_to_clipboard(
+        MainMenuCommand::CopyUsername => clipboard::copy(
             &entry
                 .get_value_with_key("username")
                 .ok_or_else(|| anyhow!("No username found in entry"))?,
+            config,
         )?,
 
-        MainMenuCommand::CopyPassword => xorg::copy_to_clipboard(
+        MainMenuCommand::CopyPassword => clipboard::copy(
             &entry
                 .get_password()
                 .ok_or_else(|| anyhow!("No password found in entry"))?,
+            config,
         )?,
 
-        MainMenuCommand::CopyOTP => xorg::copy_to_clipboard(
-            &otp::calculate_otp(
-                &entry
-                    .get_value_with_key("otp_secret")
-                    .ok_or_else(|| anyhow!("No otp_secret found in entry"))?,
-            )
-            .context("Failed to calculate otp from secret")?,
+        MainMenuCommand::CopyOTP => clipboard::copy(
+            &otp::calculate_entry_otp(&entry).context("Failed to calculate otp from secret")?,
+            config,
         )?,
 
+        MainMenuCommand::CopyLogin => {
+            let mut queue = vec![
+                clipboard::ClipText::Text(
+                    entry
+                        .get_value_with_key("username")
+                        .or_else(|| entry.get_value_with_key("email"))
+                        .ok_or_else(|| anyhow!("No username or email found in entry"))?,
+                ),
+                clipboard::ClipText::Text(
+                    entry
+                        .get_password()
+                        .ok_or_else(|| anyhow!("No password found in entry"))?,
+                ),
+            ];
+            if let Ok((secret, encoder)) = otp::entry_otp_secret(&entry) {
+                let otp = clipboard::ClipText::Otp(secret, encoder);
+                // an invalid secret fails now, rather than on the last paste
+                otp.text()?;
+                queue.push(otp);
+            }
+            clipboard::copy_queue(&queue, config)?;
+        }
+
         MainMenuCommand::OpenURLInBrowser => {
             process::Command::new(
-                config
+                &config
                     .browser
                     .clone()
-                    .ok_or_else(|| anyhow!("No browser found, please set $BROWSER"))?
-                    .to_owned(),
+                    .ok_or_else(|| anyhow!("No browser found, please set $BROWSER"))?,
             )
             .arg(
                 &entry
@@ -228,18 +844,40 @@ fn main_menu(
             .spawn()
             .context("Failed to spawn browser")?;
         }
+
+        MainMenuCommand::ToggleFavourite => {
+            toggle_favourite(config, &entry.path)?;
+            return Ok(MenuState::MainMenu);
+        }
+
+        // only folders are navigated, and entries are never folders
+        MainMenuCommand::Browse | MainMenuCommand::ParentFolder => {
+            return Ok(MenuState::MainMenu);
+        }
+
+        MainMenuCommand::MoveEntries
+        | MainMenuCommand::DeleteEntries
+        | MainMenuCommand::ReencryptEntries
+        | MainMenuCommand::ExportEntries => {
+            bulk::run_bulk_command(&command, &[entry.path], config)?;
+            return Ok(MenuState::MainMenu);
+        }
     }
 
     Ok(MenuState::Done)
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EntryMenuCommand {
     Edit,
     New,
     Delete,
     Autofill,
     Copy,
+    ImportOTP,
+    ShowQR,
+    Reveal,
+    ToggleFavourite,
 }
 
 impl fmt::Display for EntryMenuCommand {
@@ -250,33 +888,139 @@ impl fmt::Display for EntryMenuCommand {
             EntryMenuCommand::Delete => write!(f, "delete field"),
             EntryMenuCommand::Autofill => write!(f, "autofill field"),
             EntryMenuCommand::Copy => write!(f, "copy field"),
+            EntryMenuCommand::ImportOTP => write!(f, "import otp from qr code"),
+            EntryMenuCommand::ShowQR => write!(f, "show field as qr code"),
+            EntryMenuCommand::Reveal => write!(f, "reveal/hide field"),
+            EntryMenuCommand::ToggleFavourite => write!(f, "pin/unpin entry"),
+        }
+    }
+}
+
+/// The entry menu commands by their name in the configuration file, with their default keybindings.
+const ENTRY_MENU_KEYBINDINGS: &[(&str, &str, EntryMenuCommand)] = &[
+    ("new", "alt+n", EntryMenuCommand::New),
+    ("delete", "alt+d", EntryMenuCommand::Delete),
+    ("autofill", "alt+a", EntryMenuCommand::Autofill),
+    ("copy", "alt+c", EntryMenuCommand::Copy),
+    ("import_otp", "alt+q", EntryMenuCommand::ImportOTP),
+    ("show_qr", "alt+Q", EntryMenuCommand::ShowQR),
+    ("reveal", "alt+r", EntryMenuCommand::Reveal),
+    ("favourite", "alt+s", EntryMenuCommand::ToggleFavourite),
+];
+
+pub fn entry_menu_keybindings(
+    keybindings: &config::KeybindingsConfig,
+) -> Result<launcher::CustomKeybindings<EntryMenuCommand>, Error> {
+    launcher::CustomKeybindings::from_config(
+        EntryMenuCommand::Edit,
+        ENTRY_MENU_KEYBINDINGS,
+        PALETTE_KEYBINDING,
+        &keybindings.entry_menu,
+    )
+    .context("Failed to read entry menu keybindings")
+}
+
+/// Validates the configured keybindings, and warns about those which rofi
+/// already uses by default.
+pub fn check_keybindings(config: &cli::Config) -> Result<(), Error> {
+    let main_menu_keybindings = main_menu_keybindings(&config.file_config)?;
+    let entry_menu_keybindings = entry_menu_keybindings(&config.file_config.keybindings)?;
+
+    if config.launcher_kind != launcher::LauncherKind::Rofi {
+        return Ok(());
+    }
+    for binding in main_menu_keybindings
+        .bindings()
+        .into_iter()
+        .chain(entry_menu_keybindings.bindings())
+    {
+        if let Some(rofi_action) = rofi::default_binding_action(binding) {
+            let warning = format!(
+                "Warning: keybinding {:?} conflicts with rofi's default binding for {}",
+                binding, rofi_action
+            );
+            eprintln!("{}", warning);
+            if !config.no_notify {
+                // a warning, so a missing notification daemon isn't an error
+                let _ = Notification::new()
+                    .appname("pass-rofi-gui")
+                    .summary(&warning)
+                    .timeout(5000)
+                    .show();
+            }
         }
     }
+    Ok(())
+}
+
+/// The message shown above the fields of an entry, which never fails, so that
+/// entries with an invalid otp secret can still be opened and fixed.
+pub fn entry_menu_message(entry: &pass::PassEntry) -> Option<String> {
+    let (secret, encoder) = otp::entry_otp_secret(entry).ok()?;
+    match otp::calculate_otp_with_encoder(&secret, encoder) {
+        Ok(otp) => Some(format!("otp: {}", otp)),
+        Err(_) => Some("otp: invalid otp secret".to_owned()),
+    }
 }
 
 fn entry_menu(
     entry_menu_selected_index: &mut usize,
+    entry_menu_revealed_index: &mut Option<usize>,
     entry: pass::PassEntry,
     config: &cli::Config,
 ) -> Result<MenuState, Error> {
-    let keybinds = rofi::RofiCustomKeybindings::new(EntryMenuCommand::Edit)
-        .add("alt+n", EntryMenuCommand::New)?
-        .add("alt+d", EntryMenuCommand::Delete)?
-        .add("alt+a", EntryMenuCommand::Autofill)?
-        .add("alt+c", EntryMenuCommand::Copy)?;
-
-    let selected = rofi::select_item(
-        &entry.fields,
-        &config.rofi_matching,
-        *entry_menu_selected_index,
+    let keybinds = entry_menu_keybindings(&config.file_config.keybindings)?;
+    let message = entry_menu_message(&entry);
+
+    let selected = launcher::select_item(
+        &*config.launcher,
+        launcher::Dialog::EntryMenu,
+        &EntryField::from_entry(&entry, *entry_menu_revealed_index, config),
+        launcher::SelectOptions {
+            selected_index: *entry_menu_selected_index,
+            message: message.as_deref(),
+            ..Default::default()
+        },
         keybinds,
     )?;
 
     *entry_menu_selected_index = selected.index.unwrap_or_default();
-    let field = match selected.value {
-        Some(val) => val,
-        None => return Ok(MenuState::MainMenu),
+    if selected.value.is_none() {
+        return Ok(MenuState::MainMenu);
+    }
+    let command = selected
+        .command
+        .ok_or_else(|| anyhow!("No command selected"))?;
+
+    // a revealed field is hidden again by the next command
+    *entry_menu_revealed_index = match command {
+        EntryMenuCommand::Reveal => {
+            toggle_revealed(*entry_menu_revealed_index, *entry_menu_selected_index)
+        }
+        _ => None,
     };
+
+    run_entry_menu_command(entry_menu_selected_index, entry, command, config)
+}
+
+pub fn toggle_revealed(revealed_index: Option<usize>, selected_index: usize) -> Option<usize> {
+    match revealed_index == Some(selected_index) {
+        true => None,
+        false => Some(selected_index),
+    }
+}
+
+pub fn run_entry_menu_command(
+    entry_menu_selected_index: &mut usize,
+    entry: pass::PassEntry,
+    command: EntryMenuCommand,
+    config: &cli::Config,
+) -> Result<MenuState, Error> {
+    let field = entry
+        .fields
+        .get(*entry_menu_selected_index)
+        .ok_or_else(|| anyhow!("No field found at given index"))?
+        .clone();
     let field_key = match &field {
         pass::PassEntryField::Password(_) => "password",
         pass::PassEntryField::KeyVal(key, _) => key,
@@ -287,16 +1031,20 @@ fn entry_menu(
         pass::PassEntryField::KeyVal(_, val) => val,
         pass::PassEntryField::Other(val) => val,
     };
-    let command = selected
-        .command
-        .ok_or_else(|| anyhow!("Rofi command code not found"))?;
 
     match command {
         EntryMenuCommand::Edit => {
-            let new_value = match rofi::get_new_field_value(field_key, field_val)? {
-                Some(val) => val,
-                None => return Ok(MenuState::EntryMenu(entry)),
-            };
+            // masked fields aren't revealed by the editor either
+            let old_value =
+                match is_sensitive(&field, &config.file_config.entry_menu.sensitive_keys) {
+                    true => "",
+                    false => field_val,
+                };
+            let new_value =
+                match launcher::get_new_field_value(&*config.launcher, field_key, old_value)? {
+                    Some(val) => val,
+                    None => return Ok(MenuState::EntryMenu(entry)),
+                };
 
             let mut new_entry = entry.clone();
             new_entry.modify_field_value(*entry_menu_selected_index, &new_value)?;
@@ -306,22 +1054,17 @@ fn entry_menu(
         }
 
         EntryMenuCommand::New => {
-            let new_value = match rofi::get_new_field_value("new field", "")? {
+            let new_value = match launcher::get_new_field_value(&*config.launcher, "new field", "")?
+            {
                 Some(val) => val,
                 None => return Ok(MenuState::EntryMenu(entry)),
             };
 
             let mut new_entry = entry.clone();
-            let new_index = if new_entry.fields.is_empty() {
-                *entry_menu_selected_index
-            } else {
-                *entry_menu_selected_index + 1
-            };
-            new_entry.insert_new_field(new_index, &new_value);
+            *entry_menu_selected_index =
+                new_entry.insert_fields_after(*entry_menu_selected_index, &[&new_value]);
             new_entry.insert_into_store()?;
 
-            *entry_menu_selected_index += 1;
-
             return Ok(MenuState::EntryMenu(new_entry));
         }
 
@@ -337,15 +1080,310 @@ fn entry_menu(
             return Ok(MenuState::EntryMenu(new_entry));
         }
 
-        EntryMenuCommand::Autofill => xorg::type_string_in_window(
-            &xorg::get_window_id_by_user_select()
-                .context("Failed to get window_id by user selection")?,
-            &field_val,
-        )
-        .context("Failed to focus window by window_id")?,
+        EntryMenuCommand::Autofill => autofill::autofill(
+            &entry,
+            &[autofill::AutofillStep::Text(field_val.to_owned())],
+            config,
+        )?,
+
+        EntryMenuCommand::Copy => clipboard::copy(field_val, config)?,
+
+        EntryMenuCommand::ImportOTP => {
+            if otp::entry_otp_secret(&entry).is_ok() {
+                return Err(anyhow!("Entry already has an otp secret"));
+            }
+
+            let source = launcher::select_item(
+                &*config.launcher,
+                launcher::Dialog::EntryMenu,
+                &[qr::QrImageSource::File, qr::QrImageSource::ScreenRegion],
+                Default::default(),
+                launcher::CustomKeybindings::new(EntryMenuCommand::ImportOTP),
+            )?
+            .value;
+            let otpauth_uri = match source {
+                Some(qr::QrImageSource::File) => match config.launcher.get_input(
+                    launcher::Dialog::Editor,
+                    "qr image",
+                    "",
+                    "enter the path of an image containing a qr code",
+                )? {
+                    Some(image_path) => qr::decode_image_file(&image_path)?,
+                    None => return Ok(MenuState::EntryMenu(entry)),
+                },
+                Some(qr::QrImageSource::ScreenRegion) => qr::decode_image(
+                    &config
+                        .display
+                        .capture_screen_region()
+                        .context("Failed to capture screen region")?,
+                )?,
+                None => return Ok(MenuState::EntryMenu(entry)),
+            };
+
+            let mut accounts = otp::parse_otpauth_uri(&otpauth_uri)?;
+            let account = match accounts.len() {
+                1 => accounts.remove(0),
+                _ => match launcher::select_item(
+                    &*config.launcher,
+                    launcher::Dialog::EntryMenu,
+                    &accounts,
+                    Default::default(),
+                    launcher::CustomKeybindings::new(EntryMenuCommand::ImportOTP),
+                )?
+                .value
+                {
+                    Some(val) => val,
+                    None => return Ok(MenuState::EntryMenu(entry)),
+                },
+            };
+
+            otp::calculate_otp_with_encoder(&account.secret, account.encoder)
+                .context("Failed to validate otp secret")?;
+
+            let mut new_fields = vec![format!("otp_secret: {}", account.secret)];
+            if account.encoder != otp::OtpEncoder::Standard {
+                new_fields.push(format!("otp_type: {}", account.encoder));
+            }
+            let mut new_entry = entry.clone();
+            *entry_menu_selected_index =
+                new_entry.insert_fields_after(*entry_menu_selected_index, &new_fields);
+            new_entry.insert_into_store()?;
+
+            return Ok(MenuState::EntryMenu(new_entry));
+        }
+
+        EntryMenuCommand::ShowQR => {
+            let (caption, payload) = match (&field, entry.get_value_with_key("ssid")) {
+                (pass::PassEntryField::KeyVal(key, secret), _) if key == "otp_secret" => (
+                    "otp secret",
+                    otp::format_otpauth_uri(&entry.path, secret, otp::entry_otp_secret(&entry)?.1)?,
+                ),
+                (pass::PassEntryField::Password(password), Some(ssid)) => {
+                    ("wifi network", qr::wifi_payload(&ssid, password))
+                }
+                (pass::PassEntryField::KeyVal(key, ssid), Some(_)) if key == "ssid" => (
+                    "wifi network",
+                    qr::wifi_payload(ssid, &entry.get_password().unwrap_or_default()),
+                ),
+                _ => (field_key, field_val.to_owned()),
+            };
+
+            let image = qr::TempImage::new(&qr::encode_png(&payload)?)?;
+            config.launcher.show_image(
+                launcher::Dialog::EntryMenu,
+                &entry.path,
+                caption,
+                image.path_str()?,
+            )?;
 
-        EntryMenuCommand::Copy => xorg::copy_to_clipboard(&field_val)?,
+            return Ok(MenuState::EntryMenu(entry));
+        }
+
+        // the revealed field is kept by the caller
+        EntryMenuCommand::Reveal => return Ok(MenuState::EntryMenu(entry)),
+
+        EntryMenuCommand::ToggleFavourite => {
+            toggle_favourite(config, &entry.path)?;
+            return Ok(MenuState::EntryMenu(entry));
+        }
     }
 
     Ok(MenuState::Done)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xorg;
+    use launcher::Markup;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn main_menu_entry_markup() {
+        let entry = |path: &str, favourite| MainMenuEntry {
+            path: path.to_owned(),
+            label: path.to_owned(),
+            favourite,
+            folder_size: None,
+        };
+        assert_eq!(
+            entry("work/R&D/<admin>", false).markup(),
+            "<span alpha=\"50%\">work/R&amp;D/</span><b>&lt;admin&gt;</b>"
+        );
+        assert_eq!(entry("email", true).markup(), "★ <b>email</b>");
+        assert_eq!(entry("email", true).to_string(), "★ email");
+
+        let folder = MainMenuEntry {
+            path: "work/servers".to_owned(),
+            label: "servers".to_owned(),
+            favourite: false,
+            folder_size: Some(3),
+        };
+        assert_eq!(
+            folder.markup(),
+            "<b>servers/</b> <span alpha=\"50%\">(3)</span>"
+        );
+    }
+
+    #[test]
+    fn folder_tree_rows() {
+        let entries = ["web/z", "work/b/x", "email", "work/a", "work/b/y"]
+            .iter()
+            .map(|path| MainMenuEntry {
+                path: path.to_string(),
+                label: path.to_string(),
+                favourite: false,
+                folder_size: None,
+            })
+            .collect::<Vec<_>>();
+        let rows = |folder| {
+            folder_rows(entries.clone(), folder)
+                .iter()
+                .map(|row| (row.path.clone(), row.label.clone(), row.folder_size))
+                .collect::<Vec<_>>()
+        };
+        let row = |path: &str, label: &str, size| (path.to_owned(), label.to_owned(), size);
+
+        assert_eq!(
+            rows(""),
+            vec![
+                row("web", "web", Some(1)),
+                row("work", "work", Some(3)),
+                row("email", "email", None),
+            ]
+        );
+        assert_eq!(
+            rows("work"),
+            vec![row("work/b", "b", Some(2)), row("work/a", "a", None)]
+        );
+        assert_eq!(parent_folder("work/b"), "work");
+        assert_eq!(parent_folder("work"), "");
+    }
+
+    #[test]
+    fn default_action_by_folder() {
+        let file_config = config::FileConfig::from_toml(
+            r#"
+            [main_menu]
+            default_action = "autofill_custom"
+            folders = { otp = "copy_otp" }
+            "#,
+        )
+        .unwrap();
+        let main_menu_config = &file_config.main_menu;
+
+        assert_eq!(
+            default_action(main_menu_config, "otp/github.com").unwrap(),
+            MainMenuCommand::CopyOTP
+        );
+        assert_eq!(
+            default_action(main_menu_config, "web/github.com").unwrap(),
+            MainMenuCommand::AutofillCustom
+        );
+
+        let keybindings = main_menu_keybindings(&file_config).unwrap();
+        assert_eq!(keybindings.select_command(), &MainMenuCommand::Default);
+        // opening the entry menu is left to the command palette
+        assert!(keybindings.commands().contains(&MainMenuCommand::Select));
+
+        let default_keybindings = main_menu_keybindings(&Default::default()).unwrap();
+        assert_eq!(
+            default_keybindings
+                .commands()
+                .iter()
+                .filter(|command| **command == MainMenuCommand::Select)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn enter_opens_folders() {
+        let pass_store = env::temp_dir().join(format!("pass-rofi-gui-test-{}", process::id()));
+        fs::create_dir_all(pass_store.join("work")).unwrap();
+        for path in ["work/a.gpg", "work/b.gpg", "email.gpg"] {
+            fs::write(pass_store.join(path), "").unwrap();
+        }
+        let file_config = config::FileConfig::from_toml(
+            r#"
+            [main_menu]
+            browse = true
+            default_action = "autofill_password"
+            "#,
+        )
+        .unwrap();
+        let config = cli::Config {
+            browser: None,
+            no_notify: true,
+            launcher: launcher::LauncherKind::Dmenu
+                .build("normal", &file_config)
+                .unwrap(),
+            launcher_kind: launcher::LauncherKind::Dmenu,
+            display: Box::new(xorg::Xorg),
+            rofi_matching: "normal".to_owned(),
+            pass_store_path: pass_store.to_string_lossy().into_owned(),
+            file_config,
+            usage_log: false,
+            prune_usage_log: false,
+            query: None,
+            target_window: None,
+            modi: false,
+            modi_deferred: None,
+            serve_clipboard: false,
+            rofi_selection: None,
+        };
+
+        let mut level = MainMenuLevel::new(&config);
+        let mut selected_index = 0;
+        let rows = main_menu_rows(&config, &level).unwrap();
+        let command = main_menu_keybindings(&config.file_config)
+            .unwrap()
+            .select_command()
+            .clone();
+        assert_eq!(command, MainMenuCommand::AutofillPassword);
+        assert!(level
+            .navigate(&mut selected_index, &rows[0], &command, true, &config)
+            .unwrap());
+        assert_eq!(level.message().as_deref(), Some("work/"));
+
+        let rows = main_menu_rows(&config, &level).unwrap();
+        assert_eq!(rows[0].path, "work/a");
+        assert!(!level
+            .navigate(&mut selected_index, &rows[0], &command, true, &config)
+            .unwrap());
+
+        fs::remove_dir_all(pass_store).unwrap();
+    }
+
+    #[test]
+    fn sensitive_fields() {
+        let sensitive_keys = config::EntryMenuConfig::default().sensitive_keys;
+        let key_val = |key: &str| pass::PassEntryField::KeyVal(key.to_owned(), "1234".to_owned());
+
+        assert!(is_sensitive(
+            &pass::PassEntryField::Password("hunter2".to_owned()),
+            &sensitive_keys
+        ));
+        assert!(is_sensitive(&key_val("PIN"), &sensitive_keys));
+        assert!(is_sensitive(&key_val("api_token"), &sensitive_keys));
+        assert!(is_sensitive(&key_val("security answer"), &sensitive_keys));
+        assert!(!is_sensitive(&key_val("username"), &sensitive_keys));
+        assert!(!is_sensitive(&key_val("shipping"), &sensitive_keys));
+        assert!(is_sensitive(
+            &pass::PassEntryField::Other("otpauth://totp/alice?secret=ABC".to_owned()),
+            &sensitive_keys
+        ));
+    }
+
+    #[test]
+    fn masked_field_display() {
+        let field = pass::PassEntryField::KeyVal("pin".to_owned(), "1234".to_owned());
+        let masked = EntryField {
+            field: &field,
+            masked: true,
+        };
+        assert_eq!(masked.to_string(), "pin: ••••••");
+        assert!(!masked.markup().contains("1234"));
+    }
+}
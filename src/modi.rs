@@ -0,0 +1,751 @@
+//! The menus as a rofi script mode, run once per interaction. The state kept
+//! in `ROFI_DATA` never holds the contents of an entry.
+
+use crate::bulk;
+use crate::cli;
+use crate::config;
+use crate::icons;
+use crate::launcher;
+use crate::launcher::Markup;
+use crate::menu;
+use crate::pass;
+use crate::prefill;
+use crate::rofi;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use std::env;
+use std::io::Write;
+use std::iter;
+use std::os::unix;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time;
+
+const BACK_KEYBIND: &str = "alt+Left";
+
+/// The oldest rofi which passes `ROFI_DATA` to scripts.
+const MIN_ROFI_VERSION: rofi::RofiVersion = rofi::RofiVersion(1, 7, 0);
+
+const DEFERRED_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModiData {
+    state: ModiState,
+    main_menu_level: menu::MainMenuLevel,
+    main_menu_selected_index: usize,
+    entry_menu_selected_index: usize,
+    entry_menu_revealed_index: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+enum ModiState {
+    #[default]
+    MainMenu,
+    EntryMenu(String),
+    EditField(String),
+    NewField(String),
+    MainPalette(String),
+    EntryPalette(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum ModiEvent {
+    Initial,
+    Select,
+    CustomInput,
+    Keybind(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Deferred {
+    rofi_pid: u32,
+    entry_path: String,
+    main_menu_level: menu::MainMenuLevel,
+    main_menu_selected_index: usize,
+    entry_menu_selected_index: usize,
+    command: DeferredCommand,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum DeferredCommand {
+    /// The entry needs a passphrase, which can't be entered in script mode.
+    OpenEntry,
+    MainMenu(menu::MainMenuCommand),
+    EntryMenu(menu::EntryMenuCommand),
+    /// A bulk command, on these entries rather than the deferred entry.
+    Bulk(menu::MainMenuCommand, Vec<String>),
+}
+
+/// Starts rofi in script mode, with this binary as the script.
+pub fn launch(config: &cli::Config) -> Result<(), Error> {
+    rofi::RofiVersion::detect()?.require(MIN_ROFI_VERSION, "--modi")?;
+
+    let exe = env::current_exe().context("Failed to find current executable")?;
+    let exe = exe
+        .to_str()
+        .ok_or_else(|| anyhow!("Non-unicode characters in path"))?
+        .to_owned();
+    // the active window is rofi's by the time the script runs
+    let target_window = match env::args().any(|arg| arg.starts_with("--target-window")) {
+        true => None,
+        false => config.target_window.clone(),
+    };
+    let script = iter::once(exe)
+        .chain(env::args().skip(1).filter(|arg| arg != "--modi"))
+        .chain(
+            target_window
+                .into_iter()
+                .flat_map(|window_id| ["--target-window".to_owned(), window_id]),
+        )
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut command = process::Command::new("rofi");
+    command
+        .args(["-show", "pass"])
+        .args(["-modi", &format!("pass:{}", script)])
+        .arg("-i") // case-insensitive search query
+        .args(["-scroll-method", "1"]) // infinite scroll
+        .args(["-matching", &config.rofi_matching]); // matching (normal/regex/glob/fuzzy)
+
+    for (i, keybind) in custom_keybinds(&config.file_config)?.iter().enumerate() {
+        command.args([&format!("-kb-custom-{}", i + 1), keybind]);
+    }
+    if config.file_config.icons.enabled() {
+        command.arg("-show-icons");
+    }
+    let filter = prefill::initial_query(config);
+    if !filter.is_empty() {
+        command.args(["-filter", &filter]);
+    }
+    // the window persists across menus, so it takes the main menu's theme
+    command.args(config.file_config.rofi.args(launcher::Dialog::MainMenu));
+
+    command.status().context("Failed to exec rofi")?;
+    Ok(())
+}
+
+/// Handles a single script mode execution.
+pub fn run_script(config: &cli::Config) -> Result<(), Error> {
+    let keybinds = custom_keybinds(&config.file_config)?;
+
+    let event = match env::var("ROFI_RETV")
+        .context("Failed to read ROFI_RETV")?
+        .parse::<i32>()
+        .context("Failed to parse ROFI_RETV as i32")?
+    {
+        0 => ModiEvent::Initial,
+        1 => ModiEvent::Select,
+        2 => ModiEvent::CustomInput,
+        // -kb-custom-n corresponds to ROFI_RETV n+9
+        code @ 10..=28 => match keybinds.get(code as usize - 10) {
+            Some(keybind) => ModiEvent::Keybind(keybind.clone()),
+            None => ModiEvent::Initial,
+        },
+        _ => ModiEvent::Initial,
+    };
+    let data = match env::var("ROFI_DATA") {
+        Ok(data) if !data.is_empty() => {
+            serde_json::from_str(&data).context("Failed to parse ROFI_DATA")?
+        }
+        _ => ModiData {
+            main_menu_level: menu::MainMenuLevel::new(config),
+            ..Default::default()
+        },
+    };
+    let info = env::var("ROFI_INFO")
+        .ok()
+        .and_then(|info| info.parse::<usize>().ok());
+    let input = config.rofi_selection.clone().unwrap_or_default();
+
+    let data = match step(data, event, info, &input, config)? {
+        Some(data) => data,
+        None => return Ok(()),
+    };
+
+    let output = match render(&data, config)? {
+        Some(output) => output,
+        None => return Ok(()),
+    };
+    std::io::stdout()
+        .write_all(output.as_bytes())
+        .context("Failed to write to stdout")?;
+    Ok(())
+}
+
+/// Runs a command handed over from script mode, returning the menu to continue with.
+pub fn run_deferred(deferred: &str, config: &cli::Config) -> Result<menu::Menu, Error> {
+    let deferred: Deferred =
+        serde_json::from_str(deferred).context("Failed to parse deferred command")?;
+
+    // wait for rofi to close, so that it releases the keyboard
+    let rofi_proc = format!("/proc/{}", deferred.rofi_pid);
+    let start = time::Instant::now();
+    while Path::new(&rofi_proc).exists() && start.elapsed() < DEFERRED_TIMEOUT {
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    let entry = || pass::PassEntry::from_path(&deferred.entry_path, &*config.launcher);
+    let mut entry_menu_selected_index = deferred.entry_menu_selected_index;
+
+    let state = match &deferred.command {
+        DeferredCommand::OpenEntry => menu::MenuState::EntryMenu(entry()?),
+        DeferredCommand::MainMenu(command) => {
+            menu::run_main_menu_command(entry()?, command.clone(), config)?
+        }
+        DeferredCommand::EntryMenu(command) => menu::run_entry_menu_command(
+            &mut entry_menu_selected_index,
+            entry()?,
+            command.clone(),
+            config,
+        )?,
+        DeferredCommand::Bulk(command, entry_paths) => {
+            bulk::run_bulk_command(command, entry_paths, config)?;
+            entry_menu_selected_index = 0;
+            menu::MenuState::MainMenu
+        }
+    };
+
+    Ok(menu::Menu::with_state(
+        state,
+        deferred.main_menu_level,
+        deferred.main_menu_selected_index,
+        entry_menu_selected_index,
+    ))
+}
+
+fn step(
+    mut data: ModiData,
+    event: ModiEvent,
+    info: Option<usize>,
+    input: &str,
+    config: &cli::Config,
+) -> Result<Option<ModiData>, Error> {
+    if event == ModiEvent::Initial {
+        return Ok(Some(data));
+    }
+    let is_back = event == ModiEvent::Keybind(BACK_KEYBIND.to_owned());
+
+    data.state = match std::mem::take(&mut data.state) {
+        ModiState::MainMenu => {
+            let index = match info {
+                Some(index) => index,
+                None => return Ok(Some(data)),
+            };
+            let row = menu::main_menu_rows(config, &data.main_menu_level)?
+                .get(index)
+                .ok_or_else(|| anyhow!("No entry found at given index"))?
+                .clone();
+
+            data.main_menu_selected_index = index;
+            data.entry_menu_selected_index = 0;
+            data.entry_menu_revealed_index = None;
+
+            let keybindings = menu::main_menu_keybindings(&config.file_config)?;
+            let command = match &event {
+                _ if is_back => menu::MainMenuCommand::ParentFolder,
+                ModiEvent::Keybind(keybind) if keybindings.palette_binding() == Some(keybind) => {
+                    return Ok(Some(ModiData {
+                        state: ModiState::MainPalette(row.path),
+                        ..data
+                    }))
+                }
+                ModiEvent::Keybind(keybind) => match keybindings.command_for_binding(keybind) {
+                    Some(command) => command,
+                    None => return Ok(Some(data)),
+                },
+                _ => keybindings.select_command().clone(),
+            };
+
+            let entered = event == ModiEvent::Select;
+            match main_menu_command(&mut data, row, command, entered, config)? {
+                Some(state) => state,
+                None => return Ok(None),
+            }
+        }
+
+        ModiState::EntryMenu(entry_path) => {
+            let index = match (is_back, info) {
+                (true, _) => {
+                    return Ok(Some(ModiData {
+                        state: ModiState::MainMenu,
+                        main_menu_selected_index: menu::main_menu_index(
+                            config,
+                            &data.main_menu_level,
+                            &entry_path,
+                        )?,
+                        ..data
+                    }));
+                }
+                (false, Some(index)) => index,
+                (false, None) => {
+                    return Ok(Some(ModiData {
+                        state: ModiState::EntryMenu(entry_path),
+                        ..data
+                    }))
+                }
+            };
+            data.entry_menu_selected_index = index;
+
+            let keybindings = menu::entry_menu_keybindings(&config.file_config.keybindings)?;
+            let command = match &event {
+                ModiEvent::Keybind(keybind) if keybindings.palette_binding() == Some(keybind) => {
+                    return Ok(Some(ModiData {
+                        state: ModiState::EntryPalette(entry_path),
+                        ..data
+                    }))
+                }
+                // rofi binds the main menu's keys too, which do nothing here
+                ModiEvent::Keybind(keybind) => match keybindings.command_for_binding(keybind) {
+                    Some(command) => command,
+                    None => {
+                        return Ok(Some(ModiData {
+                            state: ModiState::EntryMenu(entry_path),
+                            ..data
+                        }))
+                    }
+                },
+                _ => menu::EntryMenuCommand::Edit,
+            };
+
+            match entry_menu_command(&mut data, entry_path, command, config)? {
+                Some(state) => state,
+                None => return Ok(None),
+            }
+        }
+
+        ModiState::MainPalette(_) => {
+            let command = match (is_back, info) {
+                (false, Some(index)) => menu::main_menu_keybindings(&config.file_config)?
+                    .commands()
+                    .get(index)
+                    .cloned(),
+                _ => None,
+            };
+            let row = menu::main_menu_rows(config, &data.main_menu_level)?
+                .get(data.main_menu_selected_index)
+                .cloned();
+            match (command, row) {
+                (Some(command), Some(row)) => {
+                    match main_menu_command(&mut data, row, command, false, config)? {
+                        Some(state) => state,
+                        None => return Ok(None),
+                    }
+                }
+                _ => ModiState::MainMenu,
+            }
+        }
+
+        ModiState::EntryPalette(entry_path) => {
+            let command = match (is_back, info) {
+                (false, Some(index)) => {
+                    menu::entry_menu_keybindings(&config.file_config.keybindings)?
+                        .commands()
+                        .get(index)
+                        .cloned()
+                }
+                _ => None,
+            };
+            match command {
+                Some(command) => {
+                    match entry_menu_command(&mut data, entry_path, command, config)? {
+                        Some(state) => state,
+                        None => return Ok(None),
+                    }
+                }
+                None => ModiState::EntryMenu(entry_path),
+            }
+        }
+
+        ModiState::EditField(entry_path) => {
+            let new_value = input.trim();
+            if event == ModiEvent::CustomInput && !new_value.is_empty() {
+                let mut entry = pass::PassEntry::from_path_without_pinentry(&entry_path)?;
+                entry.modify_field_value(data.entry_menu_selected_index, new_value)?;
+                entry.insert_into_store()?;
+            }
+            ModiState::EntryMenu(entry_path)
+        }
+
+        ModiState::NewField(entry_path) => {
+            let new_value = input.trim();
+            if event == ModiEvent::CustomInput && !new_value.is_empty() {
+                let mut entry = pass::PassEntry::from_path_without_pinentry(&entry_path)?;
+                data.entry_menu_selected_index =
+                    entry.insert_fields_after(data.entry_menu_selected_index, &[new_value]);
+                entry.insert_into_store()?;
+            }
+            ModiState::EntryMenu(entry_path)
+        }
+    };
+
+    Ok(Some(data))
+}
+
+/// Runs a main menu command, returning the next state, or `None` if it was
+/// handed over to run after rofi has closed.
+fn main_menu_command(
+    data: &mut ModiData,
+    row: menu::MainMenuEntry,
+    command: menu::MainMenuCommand,
+    entered: bool,
+    config: &cli::Config,
+) -> Result<Option<ModiState>, Error> {
+    // script mode has no multi-select, so bulk commands run on the selected row
+    if command.is_bulk() {
+        let entry_paths = menu::selected_entry_paths(config, std::slice::from_ref(&row))?;
+        defer(
+            data,
+            &row.path,
+            DeferredCommand::Bulk(command, entry_paths),
+            config,
+        )?;
+        return Ok(None);
+    }
+    if data.main_menu_level.navigate(
+        &mut data.main_menu_selected_index,
+        &row,
+        &command,
+        entered,
+        config,
+    )? {
+        return Ok(Some(ModiState::MainMenu));
+    }
+
+    let entry_path = row.path;
+    let command = match command {
+        menu::MainMenuCommand::Default => {
+            menu::default_action(&config.file_config.main_menu, &entry_path)?
+        }
+        command => command,
+    };
+    if command == menu::MainMenuCommand::ToggleFavourite {
+        menu::toggle_favourite(config, &entry_path)?;
+        data.main_menu_selected_index =
+            menu::main_menu_index(config, &data.main_menu_level, &entry_path)?;
+        return Ok(Some(ModiState::MainMenu));
+    }
+    menu::record_usage(config, &entry_path, &command)?;
+    match command {
+        menu::MainMenuCommand::Select => Ok(Some(ModiState::EntryMenu(entry_path))),
+        command => {
+            defer(
+                data,
+                &entry_path,
+                DeferredCommand::MainMenu(command),
+                config,
+            )?;
+            Ok(None)
+        }
+    }
+}
+
+/// Runs an entry menu command on the selected field, returning the next
+/// state, or `None` if it was handed over to run after rofi has closed.
+fn entry_menu_command(
+    data: &mut ModiData,
+    entry_path: String,
+    command: menu::EntryMenuCommand,
+    config: &cli::Config,
+) -> Result<Option<ModiState>, Error> {
+    data.entry_menu_revealed_index = match command {
+        menu::EntryMenuCommand::Reveal => menu::toggle_revealed(
+            data.entry_menu_revealed_index,
+            data.entry_menu_selected_index,
+        ),
+        _ => None,
+    };
+
+    Ok(Some(match command {
+        menu::EntryMenuCommand::Reveal => ModiState::EntryMenu(entry_path),
+        menu::EntryMenuCommand::ToggleFavourite => {
+            menu::toggle_favourite(config, &entry_path)?;
+            ModiState::EntryMenu(entry_path)
+        }
+        menu::EntryMenuCommand::Edit => ModiState::EditField(entry_path),
+        menu::EntryMenuCommand::New => ModiState::NewField(entry_path),
+        menu::EntryMenuCommand::Delete => {
+            let entry = match pass::PassEntry::from_path_without_pinentry(&entry_path) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    defer(data, &entry_path, DeferredCommand::OpenEntry, config)?;
+                    return Ok(None);
+                }
+            };
+            menu::run_entry_menu_command(
+                &mut data.entry_menu_selected_index,
+                entry,
+                menu::EntryMenuCommand::Delete,
+                config,
+            )?;
+            ModiState::EntryMenu(entry_path)
+        }
+        command => {
+            defer(
+                data,
+                &entry_path,
+                DeferredCommand::EntryMenu(command),
+                config,
+            )?;
+            return Ok(None);
+        }
+    }))
+}
+
+/// Renders the rows and mode options of the current state, or `None` if
+/// rofi should close.
+fn render(data: &ModiData, config: &cli::Config) -> Result<Option<String>, Error> {
+    let mut output = String::new();
+    let mut option = |key: &str, value: &str| {
+        // rofi reads mode options line by line, and turns `\r` into newlines
+        output.push_str(&format!("\0{}\x1f{}\n", key, value.replace('\n', "\r")))
+    };
+
+    let mut icons = Vec::new();
+    let (dialog, prompt, message, help, rows, selected_index, custom_input) = match &data.state {
+        ModiState::MainMenu => {
+            let rows = menu::main_menu_rows(config, &data.main_menu_level)?;
+            icons = menu::main_menu_icons(config, &rows)?;
+            (
+                launcher::Dialog::MainMenu,
+                "search".to_owned(),
+                data.main_menu_level.message(),
+                menu::main_menu_keybindings(&config.file_config)?.format_message(),
+                rows.iter().map(|row| row.markup()).collect(),
+                data.main_menu_selected_index,
+                false,
+            )
+        }
+
+        ModiState::EntryMenu(entry_path) => {
+            let entry = match pass::PassEntry::from_path_without_pinentry(entry_path) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    defer(data, entry_path, DeferredCommand::OpenEntry, config)?;
+                    return Ok(None);
+                }
+            };
+            icons::remember_entry_icon(&config.file_config.icons, &entry)?;
+            (
+                launcher::Dialog::EntryMenu,
+                "search".to_owned(),
+                menu::entry_menu_message(&entry),
+                format!(
+                    "{}\n{}: back",
+                    menu::entry_menu_keybindings(&config.file_config.keybindings)?.format_message(),
+                    BACK_KEYBIND
+                ),
+                menu::EntryField::from_entry(&entry, data.entry_menu_revealed_index, config)
+                    .iter()
+                    .map(|field| field.markup())
+                    .collect(),
+                data.entry_menu_selected_index,
+                false,
+            )
+        }
+
+        ModiState::EditField(entry_path) => {
+            let entry = pass::PassEntry::from_path_without_pinentry(entry_path)?;
+            let field = entry
+                .fields
+                .get(data.entry_menu_selected_index)
+                .ok_or_else(|| anyhow!("No field found at given index"))?;
+            let (field_key, field_val) = match field {
+                pass::PassEntryField::Password(val) => ("password", val),
+                pass::PassEntryField::KeyVal(key, val) => (key.as_str(), val),
+                pass::PassEntryField::Other(val) => ("string", val),
+            };
+            let (help, rows) =
+                match menu::is_sensitive(field, &config.file_config.entry_menu.sensitive_keys) {
+                    true => (
+                        format!(
+                            "type the new value and press enter to save\n{} to cancel",
+                            BACK_KEYBIND
+                        ),
+                        Vec::new(),
+                    ),
+                    false => (
+                        format!(
+                            "type the new value and press shift+enter to save\nctrl+space to edit the current value\nenter on the current value or {} to cancel",
+                            BACK_KEYBIND
+                        ),
+                        vec![field_val.to_owned()],
+                    ),
+                };
+            (
+                launcher::Dialog::Editor,
+                field_key.to_owned(),
+                None,
+                help,
+                rows,
+                0,
+                true,
+            )
+        }
+
+        ModiState::MainPalette(_) => (
+            launcher::Dialog::MainMenu,
+            "command".to_owned(),
+            None,
+            format!("{}: back", BACK_KEYBIND),
+            menu::main_menu_keybindings(&config.file_config)?
+                .commands()
+                .iter()
+                .map(|command| launcher::escape_markup(&command.to_string()))
+                .collect(),
+            0,
+            false,
+        ),
+
+        ModiState::EntryPalette(_) => (
+            launcher::Dialog::EntryMenu,
+            "command".to_owned(),
+            None,
+            format!("{}: back", BACK_KEYBIND),
+            menu::entry_menu_keybindings(&config.file_config.keybindings)?
+                .commands()
+                .iter()
+                .map(|command| launcher::escape_markup(&command.to_string()))
+                .collect(),
+            0,
+            false,
+        ),
+
+        ModiState::NewField(_) => (
+            launcher::Dialog::Editor,
+            "new field".to_owned(),
+            None,
+            format!(
+                "type the new field and press enter to save\n{} to cancel",
+                BACK_KEYBIND
+            ),
+            Vec::new(),
+            0,
+            true,
+        ),
+    };
+
+    let dialog_config = config.file_config.dialogs.dialog(dialog);
+    let prompt = dialog_config.prompt(&prompt);
+    let message = match (message, dialog_config.help(&help)) {
+        (Some(message), help) if !help.is_empty() => format!("{}\n{}", message, help),
+        (Some(message), _) => message,
+        (None, help) => help,
+    };
+
+    option("prompt", &prompt);
+    option("message", &launcher::escape_markup(&message));
+    option("use-hot-keys", "true");
+    // the editor's row stays plain, as rofi copies it into the input verbatim
+    option("markup-rows", if custom_input { "false" } else { "true" });
+    option("no-custom", if custom_input { "false" } else { "true" });
+    option(
+        "data",
+        &serde_json::to_string(data).context("Failed to serialize ROFI_DATA")?,
+    );
+    option("new-selection", &selected_index.to_string());
+
+    for (index, row) in rows.iter().enumerate() {
+        match icons.get(index) {
+            Some(Some(icon)) => {
+                output.push_str(&format!("{}\0icon\x1f{}\x1finfo\x1f{}\n", row, icon, index))
+            }
+            _ => output.push_str(&format!("{}\0info\x1f{}\n", row, index)),
+        }
+    }
+
+    Ok(Some(output))
+}
+
+/// Hands a command over to a detached copy of this process, which runs it
+/// once rofi has closed.
+fn defer(
+    data: &ModiData,
+    entry_path: &str,
+    command: DeferredCommand,
+    config: &cli::Config,
+) -> Result<(), Error> {
+    let deferred = serde_json::to_string(&Deferred {
+        rofi_pid: unix::process::parent_id(),
+        entry_path: entry_path.to_owned(),
+        main_menu_level: data.main_menu_level.clone(),
+        main_menu_selected_index: data.main_menu_selected_index,
+        entry_menu_selected_index: data.entry_menu_selected_index,
+        command,
+    })
+    .context("Failed to serialize deferred command")?;
+
+    // rofi appends the selected row to the script's arguments
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    if config.rofi_selection.is_some() {
+        args.pop();
+    }
+
+    process::Command::new(env::current_exe().context("Failed to find current executable")?)
+        .args(args)
+        .args(["--modi-deferred", &deferred])
+        .env_remove("ROFI_RETV")
+        .env_remove("ROFI_INFO")
+        .env_remove("ROFI_DATA")
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .context("Failed to spawn deferred command")?;
+
+    Ok(())
+}
+
+/// Every custom keybinding used in any menu, in the order they are passed to
+/// rofi as `-kb-custom-n`.
+fn custom_keybinds(file_config: &config::FileConfig) -> Result<Vec<String>, Error> {
+    let main_menu_keybinds = menu::main_menu_keybindings(file_config)?;
+    let entry_menu_keybinds = menu::entry_menu_keybindings(&file_config.keybindings)?;
+
+    let mut keybinds: Vec<String> = Vec::new();
+    for keybind in main_menu_keybinds
+        .bindings()
+        .into_iter()
+        .chain(entry_menu_keybinds.bindings())
+        .chain(iter::once(BACK_KEYBIND))
+    {
+        if !keybinds.iter().any(|existing| existing == keybind) {
+            keybinds.push(keybind.to_owned());
+        }
+    }
+
+    if keybinds.len() > 19 {
+        return Err(anyhow!("Max number of custom rofi keybindings exceeded"));
+    }
+    Ok(keybinds)
+}
+
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_keybinds_are_unique() {
+        let keybinds = custom_keybinds(&Default::default()).unwrap();
+        for keybind in &keybinds {
+            assert_eq!(keybinds.iter().filter(|other| *other == keybind).count(), 1);
+        }
+        // going up a folder in the main menu shares the back keybinding
+        assert!(keybinds.iter().any(|keybind| keybind == BACK_KEYBIND));
+        assert!(keybinds.len() <= 19);
+    }
+
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
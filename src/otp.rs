@@ -1,8 +1,21 @@
+use crate::pass;
+use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use miniotp::TOTP;
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::time;
+use url::Url;
 
 const OTP_DIGITS: u32 = 6;
+const OTP_PERIOD: u64 = 30;
+
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+const STEAM_DIGITS: usize = 5;
 
 pub fn calculate_otp(secret: &str) -> Result<String, Error> {
     TOTP::from_base32(secret)
@@ -11,13 +24,369 @@ pub fn calculate_otp(secret: &str) -> Result<String, Error> {
         .context("Failed to calculate OTP")
 }
 
+/// Calculates the current OTP of an entry, from either an `otp_secret` field
+/// (optionally alongside an `otp_type` field) or an `otpauth://` uri field.
+pub fn calculate_entry_otp(entry: &pass::PassEntry) -> Result<String, Error> {
+    let (secret, encoder) = entry_otp_secret(entry)?;
+
+    calculate_otp_with_encoder(&secret, encoder)
+}
+
+pub fn entry_otp_secret(entry: &pass::PassEntry) -> Result<(String, OtpEncoder), Error> {
+    let otpauth_uri = entry.fields.iter().find_map(|field| match field {
+        pass::PassEntryField::KeyVal(_, val) | pass::PassEntryField::Other(val)
+            if val.starts_with("otpauth://") =>
+        {
+            Some(val)
+        }
+        _ => None,
+    });
+
+    if let Some(otpauth_uri) = otpauth_uri {
+        let account = parse_otpauth_uri(otpauth_uri)?.remove(0);
+        return Ok((account.secret, account.encoder));
+    }
+
+    let secret = entry
+        .get_value_with_key("otp_secret")
+        .ok_or_else(|| anyhow!("No otp_secret found in entry"))?;
+    let encoder = match entry.get_value_with_key("otp_type") {
+        Some(otp_type) => otp_type.parse()?,
+        None => OtpEncoder::Standard,
+    };
+
+    Ok((secret, encoder))
+}
+
+pub fn calculate_otp_with_encoder(secret: &str, encoder: OtpEncoder) -> Result<String, Error> {
+    match encoder {
+        OtpEncoder::Standard => calculate_otp(secret),
+        OtpEncoder::Steam => {
+            let secret = base32::decode(
+                base32::Alphabet::RFC4648 { padding: false },
+                &normalize_secret(secret),
+            )
+            .ok_or_else(|| anyhow!("Failed to decode otp secret as base32"))
+            .context("Failed to calculate OTP")?;
+
+            let counter = time::SystemTime::now()
+                .duration_since(time::UNIX_EPOCH)
+                .context("Failed to calculate OTP")?
+                .as_secs()
+                / OTP_PERIOD;
+
+            Ok(format_steam_otp(truncated_hotp(&secret, counter)))
+        }
+    }
+}
+
 fn format_otp(otp: u32) -> String {
     format!("{:0width$}", otp, width = OTP_DIGITS as usize)
 }
 
+fn format_steam_otp(mut otp: u32) -> String {
+    let mut formatted = String::with_capacity(STEAM_DIGITS);
+    for _ in 0..STEAM_DIGITS {
+        let index = otp as usize % STEAM_ALPHABET.len();
+        formatted.push(STEAM_ALPHABET[index] as char);
+        otp /= STEAM_ALPHABET.len() as u32;
+    }
+    formatted
+}
+
+/// The rfc4226 dynamically truncated hmac-sha1, before it is reduced to a
+/// number of decimal digits.
+fn truncated_hotp(secret: &[u8], counter: u64) -> u32 {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = ring::hmac::sign(&key, &counter.to_be_bytes());
+    let digest = digest.as_ref();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ])
+}
+
+/// Encodes the otp value into the code the user is shown.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum OtpEncoder {
+    /// Six decimal digits, as per rfc6238.
+    Standard,
+    /// Five characters from steam guard's alphabet.
+    Steam,
+}
+
+impl FromStr for OtpEncoder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "totp" | "standard" => Ok(OtpEncoder::Standard),
+            "steam" => Ok(OtpEncoder::Steam),
+            _ => Err(anyhow!("Unsupported otp type {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for OtpEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OtpEncoder::Standard => write!(f, "totp"),
+            OtpEncoder::Steam => write!(f, "steam"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct OtpAccount {
+    pub label: String,
+    pub issuer: Option<String>,
+    pub secret: String,
+    pub encoder: OtpEncoder,
+}
+
+impl fmt::Display for OtpAccount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.issuer {
+            Some(issuer) if !self.label.starts_with(issuer.as_str()) => {
+                write!(f, "{} ({})", issuer, self.label)
+            }
+            _ => write!(f, "{}", self.label),
+        }
+    }
+}
+
+/// Parses the accounts out of an `otpauth://` or `otpauth-migration://` uri.
+pub fn parse_otpauth_uri(uri: &str) -> Result<Vec<OtpAccount>, Error> {
+    let url = Url::parse(uri.trim()).context("Failed to parse otpauth uri")?;
+
+    match url.scheme() {
+        "otpauth" => Ok(vec![parse_otpauth_url(&url)?]),
+        "otpauth-migration" => parse_otpauth_migration_url(&url),
+        scheme => Err(anyhow!("Unsupported uri scheme {:?}", scheme)),
+    }
+}
+
+/// Formats a secret as an `otpauth://` uri, as understood by phone authenticator apps.
+pub fn format_otpauth_uri(label: &str, secret: &str, encoder: OtpEncoder) -> Result<String, Error> {
+    let mut url = Url::parse("otpauth://totp/").context("Failed to build otpauth uri")?;
+    url.set_path(label);
+    url.query_pairs_mut()
+        .append_pair("secret", &normalize_secret(secret))
+        .append_pair("issuer", label);
+    if encoder == OtpEncoder::Steam {
+        url.query_pairs_mut().append_pair("encoder", "steam");
+    }
+    Ok(url.into())
+}
+
+fn parse_otpauth_url(url: &Url) -> Result<OtpAccount, Error> {
+    if url.host_str() != Some("totp") {
+        return Err(anyhow!("Only totp otpauth uris are supported"));
+    }
+
+    let label = percent_decode_str(url.path().trim_start_matches('/'))
+        .decode_utf8()
+        .context("Failed to read otpauth label as utf8")?
+        .into_owned();
+
+    let mut secret = None;
+    let mut issuer = None;
+    let mut encoder = OtpEncoder::Standard;
+    let mut digits = None;
+    for (key, value) in url.query_pairs() {
+        match (key.as_ref(), value.as_ref()) {
+            ("secret", value) => secret = Some(normalize_secret(value)),
+            ("issuer", value) => issuer = Some(value.to_owned()),
+            ("encoder", value) => encoder = value.parse()?,
+            ("digits", value) => digits = Some(value.to_owned()),
+            ("algorithm", "SHA1") => {}
+            ("period", value) if value == OTP_PERIOD.to_string() => {}
+            ("algorithm", _) | ("period", _) => {
+                return Err(anyhow!("Unsupported otpauth parameter {}={}", key, value));
+            }
+            _ => {}
+        }
+    }
+
+    // the digit count is implied by steam's encoder
+    match (encoder, digits) {
+        (OtpEncoder::Standard, Some(digits)) if digits != OTP_DIGITS.to_string() => {
+            return Err(anyhow!("Unsupported otpauth parameter digits={}", digits));
+        }
+        _ => {}
+    }
+
+    Ok(OtpAccount {
+        label,
+        issuer,
+        secret: secret.ok_or_else(|| anyhow!("No secret found in otpauth uri"))?,
+        encoder,
+    })
+}
+
+// otpauth-migration uris (as exported by google authenticator) carry a
+// base64 encoded protobuf `MigrationPayload` message in their `data` param:
+//
+//   message MigrationPayload { repeated OtpParameters otp_parameters = 1; ... }
+//   message OtpParameters {
+//     bytes secret = 1; string name = 2; string issuer = 3;
+//     Algorithm algorithm = 4; DigitCount digits = 5; OtpType type = 6; ...
+//   }
+//
+// accounts which use anything other than sha1, six digits and totp are
+// skipped.
+fn parse_otpauth_migration_url(url: &Url) -> Result<Vec<OtpAccount>, Error> {
+    use base64::Engine;
+
+    let data = url
+        .query_pairs()
+        .find_map(|(key, value)| if key == "data" { Some(value) } else { None })
+        .ok_or_else(|| anyhow!("No data found in otpauth-migration uri"))?;
+    let payload = base64::engine::general_purpose::STANDARD
+        .decode(data.as_bytes())
+        .context("Failed to decode otpauth-migration data as base64")?;
+
+    let mut accounts = Vec::new();
+    for field in ProtobufFields::new(&payload) {
+        let parameters = match field? {
+            (1, ProtobufValue::Bytes(parameters)) => parameters,
+            _ => continue,
+        };
+
+        let mut secret = None;
+        let mut label = String::new();
+        let mut issuer = None;
+        let mut supported = true;
+        for field in ProtobufFields::new(parameters) {
+            match field? {
+                (1, ProtobufValue::Bytes(val)) => secret = Some(TOTP::new(val).base32_secret()),
+                (2, ProtobufValue::Bytes(val)) => label = String::from_utf8_lossy(val).into(),
+                (3, ProtobufValue::Bytes(val)) if !val.is_empty() => {
+                    issuer = Some(String::from_utf8_lossy(val).into())
+                }
+                // algorithm: unspecified or sha1
+                (4, ProtobufValue::Varint(val)) => supported &= val <= 1,
+                // digits: unspecified or six
+                (5, ProtobufValue::Varint(val)) => supported &= val <= 1,
+                // type: totp
+                (6, ProtobufValue::Varint(val)) => supported &= val == 2,
+                _ => {}
+            }
+        }
+
+        if let (true, Some(secret)) = (supported, secret) {
+            accounts.push(OtpAccount {
+                label,
+                issuer,
+                secret,
+                encoder: OtpEncoder::Standard,
+            });
+        }
+    }
+
+    if accounts.is_empty() {
+        return Err(anyhow!(
+            "No supported accounts found in otpauth-migration uri"
+        ));
+    }
+
+    Ok(accounts)
+}
+
+fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+enum ProtobufValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the top level fields of an encoded protobuf message.
+struct ProtobufFields<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ProtobufFields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| anyhow!("Unexpected end of protobuf message"))?;
+            self.position += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Invalid protobuf varint"))
+    }
+
+    fn read_field(&mut self) -> Result<(u64, ProtobufValue<'a>), Error> {
+        let key = self.read_varint()?;
+        let value = match key & 0x7 {
+            0 => ProtobufValue::Varint(self.read_varint()?),
+            2 => {
+                let length = self.read_varint()? as usize;
+                let end = self
+                    .position
+                    .checked_add(length)
+                    .filter(|end| *end <= self.data.len())
+                    .ok_or_else(|| anyhow!("Unexpected end of protobuf message"))?;
+                let bytes = &self.data[self.position..end];
+                self.position = end;
+                ProtobufValue::Bytes(bytes)
+            }
+            wire_type @ 1 | wire_type @ 5 => {
+                self.position += if wire_type == 1 { 8 } else { 4 };
+                ProtobufValue::Fixed
+            }
+            wire_type => return Err(anyhow!("Unsupported protobuf wire type {}", wire_type)),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for ProtobufFields<'a> {
+    type Item = Result<(u64, ProtobufValue<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.data.len() {
+            return None;
+        }
+        let field = self.read_field();
+        if field.is_err() {
+            self.position = self.data.len();
+        }
+        Some(field)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::format_otp;
+    use super::format_otpauth_uri;
+    use super::format_steam_otp;
+    use super::parse_otpauth_uri;
+    use super::truncated_hotp;
+    use super::OtpAccount;
+    use super::OtpEncoder;
 
     #[test]
     fn test_format_otp_6_digits() {
@@ -33,4 +402,96 @@ mod tests {
             assert_eq!(format_otp(test.0), test.1);
         }
     }
+
+    #[test]
+    fn test_truncated_hotp() {
+        // rfc4226 appendix d test values
+        let secret = b"12345678901234567890";
+        let tests = [(0, 0x4c93cf18), (1, 0x41397eea), (9, 0x2679dc69)];
+
+        for test in &tests {
+            assert_eq!(truncated_hotp(secret, test.0), test.1);
+        }
+    }
+
+    #[test]
+    fn test_format_steam_otp() {
+        assert_eq!(format_steam_otp(0), "22222");
+        assert_eq!(format_steam_otp(1), "32222");
+        assert_eq!(format_steam_otp(26), "23222");
+        assert_eq!(format_steam_otp(0x4c93cf18), "GG5F5");
+    }
+
+    #[test]
+    fn test_parse_otpauth_uri_steam() {
+        let accounts = parse_otpauth_uri(
+            "otpauth://totp/Steam:alice?secret=JBSWY3DPEHPK3PXP&digits=5&encoder=steam",
+        )
+        .unwrap();
+
+        assert_eq!(accounts[0].encoder, OtpEncoder::Steam);
+        assert!(parse_otpauth_uri("otpauth://totp/Steam?secret=A&encoder=yandex").is_err());
+    }
+
+    #[test]
+    fn test_parse_otpauth_uri() {
+        let accounts = parse_otpauth_uri(
+            "otpauth://totp/Example:alice%40example.com?secret=jbsw y3dp ehpk 3pxp&issuer=Example",
+        )
+        .unwrap();
+
+        assert_eq!(
+            accounts,
+            vec![OtpAccount {
+                label: "Example:alice@example.com".to_owned(),
+                issuer: Some("Example".to_owned()),
+                secret: "JBSWY3DPEHPK3PXP".to_owned(),
+                encoder: OtpEncoder::Standard,
+            }]
+        );
+
+        assert!(parse_otpauth_uri("otpauth://hotp/Example?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/Example?secret=A&digits=8").is_err());
+        assert!(parse_otpauth_uri("otpauth://totp/Example?issuer=Example").is_err());
+    }
+
+    #[test]
+    fn test_format_otpauth_uri_roundtrip() {
+        let uri = format_otpauth_uri(
+            "web/example.com",
+            "jbsw y3dp ehpk 3pxp",
+            OtpEncoder::Standard,
+        )
+        .unwrap();
+
+        assert_eq!(
+            parse_otpauth_uri(&uri).unwrap(),
+            vec![OtpAccount {
+                label: "web/example.com".to_owned(),
+                issuer: Some("web/example.com".to_owned()),
+                secret: "JBSWY3DPEHPK3PXP".to_owned(),
+                encoder: OtpEncoder::Standard,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_otpauth_migration_uri() {
+        // two accounts: a totp account `alice` (issuer `Example`, secret
+        // "Hello!\xde\xad\xbe\xef") and an unsupported hotp account `bob`
+        let accounts = parse_otpauth_uri(
+            "otpauth-migration://offline?data=CiIKCkhlbGxvId6tvu8SBWFsaWNlGgdFeGFtcGxlIAEoATACCg8KAgEBEgNib2IgASgBMAE%3D",
+        )
+        .unwrap();
+
+        assert_eq!(
+            accounts,
+            vec![OtpAccount {
+                label: "alice".to_owned(),
+                issuer: Some("Example".to_owned()),
+                secret: "JBSWY3DPEHPK3PXP".to_owned(),
+                encoder: OtpEncoder::Standard,
+            }]
+        );
+    }
 }
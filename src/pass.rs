@@ -1,4 +1,4 @@
-use crate::rofi;
+use crate::launcher;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
@@ -16,11 +16,11 @@ pub struct PassEntry {
 }
 
 impl PassEntry {
-    pub fn from_path(entry_path: &str) -> Result<Self, Error> {
+    pub fn from_path(entry_path: &str, launcher: &dyn launcher::Launcher) -> Result<Self, Error> {
         if let Ok(val) = Self::from_path_without_pinentry(entry_path) {
             return Ok(val);
         }
-        Self::from_path_with_pinentry(entry_path)
+        Self::from_path_with_pinentry(entry_path, launcher)
     }
 
     pub fn get_password(&self) -> Option<String> {
@@ -59,6 +59,24 @@ impl PassEntry {
         self.fields.insert(index, new_field);
     }
 
+    /// Inserts fields after the selected one, returning the index of the first.
+    /// Empty entries get a blank password line first, as pass reads the first
+    /// line as the password.
+    pub fn insert_fields_after(
+        &mut self,
+        selected_index: usize,
+        new_fields: &[impl AsRef<str>],
+    ) -> usize {
+        if self.fields.is_empty() {
+            self.fields.push(PassEntryField::Password(String::new()));
+        }
+        let new_index = selected_index + 1;
+        for (offset, new_field) in new_fields.iter().enumerate() {
+            self.insert_new_field(new_index + offset, new_field.as_ref());
+        }
+        new_index
+    }
+
     pub fn modify_field_value(&mut self, field_index: usize, new_value: &str) -> Result<(), Error> {
         let field = self
             .fields
@@ -127,10 +145,10 @@ impl PassEntry {
         }
     }
 
-    fn from_path_without_pinentry(entry_path: &str) -> Result<Self, Error> {
+    pub fn from_path_without_pinentry(entry_path: &str) -> Result<Self, Error> {
         let output = process::Command::new("pass")
             .env("PASSWORD_STORE_GPG_OPTS", "--pinentry-mode loopback")
-            .args(&["show", entry_path])
+            .args(["show", entry_path])
             .output()
             .context("Failed to execute pass")?;
 
@@ -139,13 +157,16 @@ impl PassEntry {
             None => panic!("pass exit code was None not 0"),
             Some(0) => Ok(Self::from_output(entry_path, output.stdout)?),
             Some(2) => Err(anyhow!("Pinentry required")),
-            Some(val) => panic!(format!("pass exit code was {} not 0", val)),
+            Some(val) => panic!("pass exit code was {} not 0", val),
         }
     }
 
-    fn from_path_with_pinentry(entry_path: &str) -> Result<Self, Error> {
-        let passphrase =
-            rofi::get_passphrase()?.ok_or_else(|| anyhow!("Failed to get passphrase via rofi"))?;
+    fn from_path_with_pinentry(
+        entry_path: &str,
+        launcher: &dyn launcher::Launcher,
+    ) -> Result<Self, Error> {
+        let passphrase = launcher::get_passphrase(launcher)?
+            .ok_or_else(|| anyhow!("Failed to get passphrase via launcher"))?;
 
         let mut child = process::Command::new("pass")
             .stdin(process::Stdio::piped())
@@ -154,7 +175,7 @@ impl PassEntry {
                 "PASSWORD_STORE_GPG_OPTS",
                 "--pinentry-mode loopback --passphrase-fd=0",
             )
-            .args(&["show", entry_path])
+            .args(["show", entry_path])
             .spawn()
             .context("Failed to spawn pass")?;
 
@@ -175,7 +196,7 @@ impl PassEntry {
             None => panic!("pass exit code was None not 0"),
             Some(0) => Ok(Self::from_output(entry_path, output.stdout)?),
             Some(2) => Err(anyhow!("Invalid passphrase provided")),
-            Some(val) => panic!(format!("pass exit code was {} not 0", val)),
+            Some(val) => panic!("pass exit code was {} not 0", val),
         }
     }
 
@@ -234,7 +255,7 @@ impl PassStoreDirectory {
         let pass_store = Path::new(pass_store_path);
 
         let mut entry_paths = Vec::new();
-        entry_paths = Self::recurse_pass_store(&pass_store, &pass_store, entry_paths)
+        entry_paths = Self::recurse_pass_store(pass_store, pass_store, entry_paths)
             .context("Failed to recurse pass store")?;
         entry_paths.sort();
 
@@ -259,6 +280,44 @@ impl PassStoreDirectory {
         Self::new(&pass_store_path)
     }
 
+    /// Moves an entry with `pass mv`, refusing to overwrite an existing entry.
+    pub fn move_entry(
+        pass_store_path: &str,
+        entry_path: &str,
+        new_entry_path: &str,
+    ) -> Result<(), Error> {
+        if Path::new(pass_store_path)
+            .join(format!("{}.gpg", new_entry_path))
+            .exists()
+        {
+            return Err(anyhow!("Entry {:?} already exists", new_entry_path));
+        }
+        run_pass(&["mv", entry_path, new_entry_path])
+    }
+
+    pub fn remove_entry(entry_path: &str) -> Result<(), Error> {
+        run_pass(&["rm", "--force", entry_path])
+    }
+
+    /// Copies the encrypted file of an entry to the same path in another
+    /// directory, without decrypting it.
+    pub fn export_entry(
+        pass_store_path: &str,
+        entry_path: &str,
+        export_path: &str,
+    ) -> Result<(), Error> {
+        let file_name = format!("{}.gpg", entry_path);
+        let source = Path::new(pass_store_path).join(&file_name);
+        let destination = Path::new(export_path).join(&file_name);
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        fs::copy(&source, &destination)
+            .with_context(|| format!("Failed to copy {:?} to {:?}", source, destination))?;
+        Ok(())
+    }
+
     fn recurse_pass_store(
         pass_store: &Path,
         directory: &Path,
@@ -273,7 +332,7 @@ impl PassStoreDirectory {
             // ignore paths beginning with '.'
             if path
                 .components()
-                .last()
+                .next_back()
                 .ok_or_else(|| anyhow!("Failed to read path"))?
                 .as_os_str()
                 .to_str()
@@ -286,7 +345,7 @@ impl PassStoreDirectory {
 
             // recurse subdirectories
             if path.is_dir() {
-                pass_entries = Self::recurse_pass_store(&pass_store, &path, pass_entries)?;
+                pass_entries = Self::recurse_pass_store(pass_store, &path, pass_entries)?;
                 continue;
             }
 
@@ -317,3 +376,18 @@ impl PassStoreDirectory {
         Ok(pass_entries)
     }
 }
+
+fn run_pass(args: &[&str]) -> Result<(), Error> {
+    let status = process::Command::new("pass")
+        .args(args)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .status()
+        .context("Failed to execute pass")?;
+
+    match status.code() {
+        Some(0) => Ok(()),
+        None => Err(anyhow!("Pass exited with no status code")),
+        Some(val) => Err(anyhow!("Pass exited with non-zero status code {}", val)),
+    }
+}
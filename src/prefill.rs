@@ -0,0 +1,152 @@
+//! The initial search query of the main menu, from `--query` or the window
+//! which was active when the menu opened.
+
+use crate::cli;
+use crate::config;
+use crate::display;
+use anyhow::Error;
+use std::collections::BTreeMap;
+
+/// Browsers, whose titles are the page title and then their own name.
+const DEFAULT_RULES: &[(&str, &str)] = &[
+    ("firefox", "title"),
+    ("librewolf", "title"),
+    ("chromium", "title"),
+    ("google-chrome", "title"),
+    ("brave-browser", "title"),
+    ("vivaldi-stable", "title"),
+    ("qutebrowser", "title"),
+];
+
+/// Separators between the parts of window titles, which are all read as the first.
+const TITLE_SEPARATORS: &[&str] = &[" - ", " — ", " – ", " | ", " · "];
+
+pub fn initial_query(config: &cli::Config) -> String {
+    if let Some(query) = &config.query {
+        return query.to_owned();
+    }
+    if !config.file_config.prefill.active_window {
+        return String::new();
+    }
+    // the query only saves typing, so the menu opens without one if there is
+    // no active window or xdotool is missing
+    active_window_query(&config.file_config.prefill, &*config.display).unwrap_or_default()
+}
+
+fn active_window_query(
+    prefill_config: &config::PrefillConfig,
+    display: &dyn display::DisplayServer,
+) -> Result<String, Error> {
+    let window_id = display.get_active_window_id()?;
+    let class_names = display.get_window_class(&window_id)?;
+
+    Ok(match find_rule(&prefill_config.classes, &class_names) {
+        None | Some("") => String::new(),
+        Some("title") => {
+            query_from_title(&display.get_window_name(&window_id)?.unwrap_or_default())
+        }
+        Some("class") => class_names
+            .last()
+            .cloned()
+            .unwrap_or_default()
+            .to_lowercase(),
+        Some(query) => query.to_owned(),
+    })
+}
+
+/// The configured rule for any of a window's class names, or else the default one.
+fn find_rule<'a>(rules: &'a BTreeMap<String, String>, class_names: &[String]) -> Option<&'a str> {
+    let configured = class_names.iter().find_map(|class_name| {
+        rules
+            .iter()
+            .find(|(class, _)| class.eq_ignore_ascii_case(class_name))
+            .map(|(_, rule)| rule.as_str())
+    });
+    configured.or_else(|| {
+        class_names.iter().find_map(|class_name| {
+            DEFAULT_RULES
+                .iter()
+                .find(|(class, _)| class.eq_ignore_ascii_case(class_name))
+                .map(|(_, rule)| *rule)
+        })
+    })
+}
+
+/// A domain in the title, such as of pages which show their address, or else
+/// the site name: the last single word part of the title, before the last
+/// part, which is the program's name.
+fn query_from_title(title: &str) -> String {
+    if let Some(domain) = title.split_whitespace().find_map(domain) {
+        return domain;
+    }
+
+    let title = TITLE_SEPARATORS[1..]
+        .iter()
+        .fold(title.to_owned(), |title, separator| {
+            title.replace(separator, TITLE_SEPARATORS[0])
+        });
+    let mut parts = title
+        .split(TITLE_SEPARATORS[0])
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    parts.pop();
+
+    parts
+        .iter()
+        .rev()
+        .find(|part| !part.contains(' '))
+        .or_else(|| parts.last())
+        .map(|part| part.to_lowercase())
+        .unwrap_or_default()
+}
+
+/// The domain of a word which is a URL, an email address or a domain, without `www.`.
+pub fn domain(word: &str) -> Option<String> {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let word = word.split_once("://").map_or(word, |(_, rest)| rest);
+    let word = word.rsplit_once('@').map_or(word, |(_, rest)| rest);
+    let host = word.split(['/', ':', '?', '#']).next()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+
+    let labels = host.split('.').collect::<Vec<_>>();
+    let tld = labels.last()?;
+    let valid = labels.len() > 1
+        && labels.iter().all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && tld.len() >= 2
+        && tld.chars().all(|c| c.is_ascii_alphabetic());
+    valid.then(|| host.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_from_titles() {
+        for (title, query) in [
+            (
+                "GitHub - rust-lang/rust: Empowering everyone — Mozilla Firefox",
+                "github",
+            ),
+            (
+                "Issues · rust-lang/rust · GitHub — Mozilla Firefox",
+                "github",
+            ),
+            (
+                "Inbox (3) - alice@gmail.com - Gmail - Google Chrome",
+                "gmail.com",
+            ),
+            (
+                "https://www.example.org/login?next=/ - Chromium",
+                "example.org",
+            ),
+            ("Log in | Example Bank - Brave", "example bank"),
+            ("Mozilla Firefox", ""),
+        ] {
+            assert_eq!(query_from_title(title), query, "{:?}", title);
+        }
+    }
+}
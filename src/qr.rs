@@ -0,0 +1,171 @@
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use image::ImageEncoder;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process;
+
+const QR_MODULE_SIZE: u32 = 8;
+const QR_QUIET_ZONE: u32 = 4;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum QrImageSource {
+    File,
+    ScreenRegion,
+}
+
+impl fmt::Display for QrImageSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QrImageSource::File => write!(f, "image file"),
+            QrImageSource::ScreenRegion => write!(f, "screen region"),
+        }
+    }
+}
+
+pub fn decode_image_file(image_path: &str) -> Result<String, Error> {
+    let image_data =
+        fs::read(image_path).with_context(|| format!("Failed to read {:?}", image_path))?;
+
+    decode_image(&image_data)
+}
+
+pub fn decode_image(image_data: &[u8]) -> Result<String, Error> {
+    let image = image::load_from_memory(image_data)
+        .context("Failed to load qr code image")?
+        .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+
+    let grid = prepared
+        .detect_grids()
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No qr code found in image"))?;
+
+    let (_, content) = grid.decode().context("Failed to decode qr code")?;
+
+    Ok(content)
+}
+
+pub fn encode_png(data: &str) -> Result<Vec<u8>, Error> {
+    let code = qrcode::QrCode::new(data.as_bytes()).context("Failed to encode qr code")?;
+    let modules = code.to_colors();
+    let width = code.width() as u32;
+
+    let size = (width + 2 * QR_QUIET_ZONE) * QR_MODULE_SIZE;
+    let image = image::GrayImage::from_fn(size, size, |x, y| {
+        let x = (x / QR_MODULE_SIZE).checked_sub(QR_QUIET_ZONE);
+        let y = (y / QR_MODULE_SIZE).checked_sub(QR_QUIET_ZONE);
+        match (x, y) {
+            (Some(x), Some(y))
+                if x < width
+                    && y < width
+                    && modules[(y * width + x) as usize] == qrcode::Color::Dark =>
+            {
+                image::Luma([0])
+            }
+            _ => image::Luma([255]),
+        }
+    });
+
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png)
+        .write_image(&image, size, size, image::ExtendedColorType::L8)
+        .context("Failed to encode qr code as png")?;
+    Ok(png)
+}
+
+/// Formats a wifi network as a `WIFI:` payload, as understood by phone cameras.
+pub fn wifi_payload(ssid: &str, password: &str) -> String {
+    fn escape(value: &str) -> String {
+        value
+            .chars()
+            .flat_map(|c| match c {
+                '\\' | ';' | ',' | ':' | '"' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect()
+    }
+
+    match password {
+        "" => format!("WIFI:T:nopass;S:{};;", escape(ssid)),
+        password => format!("WIFI:T:WPA;S:{};P:{};;", escape(ssid), escape(password)),
+    }
+}
+
+/// A png image written to tmpfs, which is overwritten and removed on drop.
+#[derive(Debug)]
+pub struct TempImage {
+    pub path: PathBuf,
+}
+
+impl TempImage {
+    pub fn new(png: &[u8]) -> Result<Self, Error> {
+        let directory = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/dev/shm".to_owned());
+        let path = PathBuf::from(directory).join(format!("pass-rofi-gui-{}.png", process::id()));
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to create {:?}", path))?;
+
+        let temp_image = Self { path };
+        file.write_all(png)
+            .with_context(|| format!("Failed to write {:?}", temp_image.path))?;
+
+        Ok(temp_image)
+    }
+
+    pub fn path_str(&self) -> Result<&str, Error> {
+        self.path
+            .to_str()
+            .ok_or_else(|| anyhow!("Non-unicode characters in path"))
+    }
+}
+
+impl Drop for TempImage {
+    fn drop(&mut self) {
+        if let Ok(mut file) = fs::OpenOptions::new().write(true).open(&self.path) {
+            if let Ok(metadata) = file.metadata() {
+                let _ = file.write_all(&vec![0; metadata.len() as usize]);
+                let _ = file.sync_all();
+            }
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_image;
+    use super::encode_png;
+    use super::wifi_payload;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let data = "otpauth://totp/Example?secret=JBSWY3DPEHPK3PXP";
+
+        assert_eq!(decode_image(&encode_png(data).unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_wifi_payload() {
+        assert_eq!(
+            wifi_payload("home;net", "pa:ss"),
+            "WIFI:T:WPA;S:home\\;net;P:pa\\:ss;;"
+        );
+        assert_eq!(wifi_payload("guest", ""), "WIFI:T:nopass;S:guest;;");
+    }
+}
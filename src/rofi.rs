@@ -1,52 +1,190 @@
+use crate::config;
+use crate::launcher;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
+use std::cell::OnceCell;
 use std::fmt;
 use std::io::Write;
 use std::process;
 use std::str::FromStr;
 
-pub fn select_item<TValue: fmt::Display + Clone, TCommand: fmt::Display + Clone>(
-    items: &[TValue],
-    matching: &str,
-    selected_index: usize,
-    custom_keybindings: RofiCustomKeybindings<TCommand>,
-) -> Result<RofiSelectedItem<TValue, TCommand>, Error> {
-    RofiSelectedItem::from_items(items, matching, selected_index, custom_keybindings)
+/// The oldest rofi with every option used by the menus (`-kb-custom-19` and `-format i`).
+pub const MIN_VERSION: RofiVersion = RofiVersion(1, 5, 0);
+
+/// The rofi which dropped `-lines` and `-width` in favour of theme properties.
+const THEME_SIZE_VERSION: RofiVersion = RofiVersion(1, 7, 0);
+
+/// The rofi which added the `element-icon` widget, which images are drawn in.
+const IMAGE_VERSION: RofiVersion = RofiVersion(1, 6, 0);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct RofiVersion(pub u32, pub u32, pub u32);
+
+impl RofiVersion {
+    /// Reads the version of the installed rofi from `rofi -version`.
+    pub fn detect() -> Result<Self, Error> {
+        let output = process::Command::new("rofi")
+            .arg("-version")
+            .output()
+            .context("Failed to exec rofi")?;
+
+        String::from_utf8(output.stdout)
+            .context("Failed to read output as utf8")?
+            .parse()
+    }
+
+    pub fn require(self, min_version: Self, feature: &str) -> Result<Self, Error> {
+        if self < min_version {
+            return Err(anyhow!(
+                "rofi {} is too old for {}, please upgrade to rofi {} or newer",
+                self,
+                feature,
+                min_version
+            ));
+        }
+        Ok(self)
+    }
+}
+
+impl FromStr for RofiVersion {
+    type Err = Error;
+
+    /// Parses the output of `rofi -version`, e.g. `Version: 1.7.5+wayland2`
+    /// or `1.5.4-60-gdc1a1b6e (makepkg)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s
+            .split_whitespace()
+            .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+            .ok_or_else(|| anyhow!("Failed to find rofi version in {:?}", s.trim()))?;
+
+        let mut parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|part| part.parse::<u32>().unwrap_or(0));
+
+        Ok(RofiVersion(
+            parts.next().unwrap_or(0),
+            parts.next().unwrap_or(0),
+            parts.next().unwrap_or(0),
+        ))
+    }
+}
+
+impl fmt::Display for RofiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
 }
 
 #[derive(Debug)]
-pub struct RofiSelectedItem<TValue: fmt::Display + Clone, TCommand: fmt::Display + Clone> {
-    pub index: Option<usize>,
-    pub value: Option<TValue>,
-    pub command: Option<TCommand>,
+pub struct Rofi {
+    matching: String,
+    config: config::RofiConfig,
+    /// Detected when the first dialog is shown, as script mode steps and the
+    /// clipboard server never show one.
+    version: OnceCell<RofiVersion>,
 }
 
-impl<TValue: fmt::Display + Clone, TCommand: fmt::Display + Clone>
-    RofiSelectedItem<TValue, TCommand>
-{
-    pub fn from_items(
-        items: &[TValue],
-        matching: &str,
-        selected_index: usize,
-        custom_keybindings: RofiCustomKeybindings<TCommand>,
-    ) -> Result<Self, Error> {
+impl Rofi {
+    pub fn new(matching: &str, config: config::RofiConfig) -> Result<Self, Error> {
+        Ok(Self {
+            matching: matching.to_owned(),
+            config,
+            version: OnceCell::new(),
+        })
+    }
+
+    fn version(&self) -> Result<RofiVersion, Error> {
+        if let Some(version) = self.version.get() {
+            return Ok(*version);
+        }
+        let version = RofiVersion::detect()?.require(MIN_VERSION, "pass-rofi-gui")?;
+        Ok(*self.version.get_or_init(|| version))
+    }
+
+    /// A rofi dmenu, once the installed rofi is known to be new enough.
+    fn command(&self) -> Result<process::Command, Error> {
+        self.version()?;
+
         let mut command = process::Command::new("rofi");
         command
             .stdin(process::Stdio::piped())
             .stdout(process::Stdio::piped())
-            .arg("-dmenu")
+            .arg("-dmenu");
+        Ok(command)
+    }
+
+    /// Sizes the dialogs without a list, or with a single row, in the dialect
+    /// of the installed rofi.
+    fn size_args(&self, lines: u32, width: u32) -> Result<Vec<String>, Error> {
+        let args = match self.version()? >= THEME_SIZE_VERSION {
+            true => vec![
+                "-theme-str".to_owned(),
+                format!(
+                    "window {{ width: {}%; }} listview {{ lines: {}; }}",
+                    width, lines
+                ),
+            ],
+            false => vec![
+                "-lines".to_owned(),
+                lines.to_string(),
+                "-width".to_owned(),
+                width.to_string(),
+            ],
+        };
+        Ok(args)
+    }
+
+    /// Appends the configured theme and arguments, which come last so that
+    /// they override the built-in ones.
+    fn apply_config(&self, command: &mut process::Command, dialog: launcher::Dialog) {
+        command.args(self.config.args(dialog));
+    }
+}
+
+impl launcher::Launcher for Rofi {
+    fn supports_custom_keybindings(&self) -> bool {
+        true
+    }
+
+    fn select(&self, request: &launcher::SelectRequest) -> Result<launcher::Selection, Error> {
+        let mut command = self.command()?;
+        command
             .arg("-i") // case-insensitive search query
-            .args(&["-scroll-method", "1"]) // infinite scroll
-            .args(&["-selected-row", &selected_index.to_string()])
-            .args(&["-matching", matching]) // matching (normal/regex/glob/fuzzy)
-            .args(&["-p", "search"]) // prompt text
-            .args(&["-format", "i"]) // output index of selected entry
-            .args(&["-mesg", &custom_keybindings.format_message()]);
-
-        for (i, keybind) in custom_keybindings.keybinds().iter().enumerate() {
-            command.args(&[format!("-kb-custom-{}", i + 1), keybind.binding.clone()]);
+            .args(["-scroll-method", "1"]) // infinite scroll
+            .args(["-selected-row", &request.selected_index.to_string()])
+            .args(["-matching", &self.matching]) // matching (normal/regex/glob/fuzzy)
+            .args(["-p", request.prompt]) // prompt text
+            .args(["-format", "i"]); // output index of selected entry
+
+        if !request.icons.is_empty() {
+            command.arg("-show-icons");
         }
+        if request.multi_select {
+            command.arg("-multi-select");
+        }
+        if !request.filter.is_empty() {
+            command.args(["-filter", request.filter]);
+        }
+        let rows = match request.markup_rows.is_empty() {
+            true => request.rows,
+            false => {
+                command.arg("-markup-rows");
+                request.markup_rows
+            }
+        };
+
+        // rofi draws the message as Pango markup, and it may hold folder names
+        let message = request.message_with_help();
+        if !message.is_empty() {
+            command.args(["-mesg", &launcher::escape_markup(&message)]);
+        }
+
+        for (i, keybind) in request.keybinds.iter().enumerate() {
+            command.args([&format!("-kb-custom-{}", i + 1), *keybind]);
+        }
+        self.apply_config(&mut command, request.dialog);
 
         let mut child = command.spawn().context("Failed to spawn rofi")?;
 
@@ -55,9 +193,13 @@ impl<TValue: fmt::Display + Clone, TCommand: fmt::Display + Clone>
             .as_mut()
             .ok_or_else(|| anyhow!("Failed to open rofi stdin"))?;
 
-        for item in items {
+        for (index, row) in rows.iter().enumerate() {
+            let row = match request.icons.get(index) {
+                Some(Some(icon)) => format!("{}\0icon\x1f{}\n", row, icon),
+                _ => format!("{}\n", row),
+            };
             stdin
-                .write_all(format!("{}\n", item).as_bytes())
+                .write_all(row.as_bytes())
                 .context("Failed to write to rofi stdin")?;
         }
 
@@ -65,175 +207,228 @@ impl<TValue: fmt::Display + Clone, TCommand: fmt::Display + Clone>
             .wait_with_output()
             .context("Failed to read rofi stdout")?;
 
-        let item_index = match String::from_utf8(output.stdout)
+        // one index per line, as several rows can be selected with -multi-select
+        let indices = String::from_utf8(output.stdout)
             .context("Failed to read output as utf8")?
-            .trim()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| usize::from_str(line.trim()).context("Failed to parse item index as usize"))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(launcher::Selection {
+            indices,
+            command: exit_code_to_command(output.status.code()),
+        })
+    }
+
+    fn get_input(
+        &self,
+        dialog: launcher::Dialog,
+        prompt: &str,
+        initial_value: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut command = self.command()?;
+        command
+            .args(["-input", "/dev/null"])
+            .args(self.size_args(0, 20)?)
+            .arg("-disable-history")
+            .args(["-p", prompt])
+            .args(["-filter", initial_value])
+            .args(["-mesg", &launcher::escape_markup(message)]);
+        self.apply_config(&mut command, dialog);
+
+        let new_value = match String::from_utf8(
+            command
+                .spawn()
+                .context("Failed to spawn rofi")?
+                .wait_with_output()
+                .context("Failed to read rofi stdout")?
+                .stdout,
+        )
+        .context("Failed to read new value as utf8")?
+        .trim()
         {
             "" => None,
-            val => Some(usize::from_str(val).context("Failed to parse item index as usize")?),
+            val => Some(val.to_owned()),
         };
+        Ok(new_value)
+    }
+
+    fn get_password(
+        &self,
+        dialog: launcher::Dialog,
+        prompt: &str,
+        message: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut command = self.command()?;
+        command
+            .args(["-input", "/dev/null"])
+            .args(self.size_args(0, 20)?)
+            .arg("-disable-history")
+            .arg("-password")
+            .args(["-p", prompt])
+            .args(["-mesg", &launcher::escape_markup(message)]);
+        self.apply_config(&mut command, dialog);
 
-        let item = match item_index {
-            Some(item_index) => Some(
-                items
-                    .get(item_index)
-                    .ok_or_else(|| {
-                        anyhow!("Failed to index item using index value from rofi output")
-                    })?
-                    .clone(),
-            ),
-            None => None,
+        let passphrase = match String::from_utf8(
+            command
+                .spawn()
+                .context("Failed to spawn rofi")?
+                .wait_with_output()
+                .context("Failed to read rofi stdout")?
+                .stdout,
+        )
+        .context("Failed to read passphrase as utf8")?
+        .trim()
+        {
+            "" => None,
+            val => Some(val.to_owned()),
         };
+        Ok(passphrase)
+    }
 
-        let command = custom_keybindings.exit_code_to_command(output.status.code());
+    fn show_image(
+        &self,
+        dialog: launcher::Dialog,
+        prompt: &str,
+        caption: &str,
+        image_path: &str,
+    ) -> Result<(), Error> {
+        self.version()?.require(IMAGE_VERSION, "showing images")?;
 
-        Ok(Self {
-            index: item_index,
-            value: item,
-            command,
-        })
-    }
-}
+        let mut command = self.command()?;
+        command
+            .arg("-show-icons")
+            .arg("-disable-history")
+            .args(self.size_args(1, 30)?)
+            .args(["-p", prompt])
+            .args([
+                "-theme-str",
+                "element { orientation: vertical; } element-icon { size: 20em; }",
+            ])
+            .args(["-mesg", "esc to close"]);
+        self.apply_config(&mut command, dialog);
+        let mut child = command.spawn().context("Failed to spawn rofi")?;
 
-#[derive(Debug)]
-pub struct RofiCustomKeybindings<TCommand: fmt::Display + Clone> {
-    select_command: TCommand,
-    keybinds: Vec<Keybind<TCommand>>,
-}
+        let stdin = child
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("Failed to open rofi stdin"))?;
+        stdin
+            .write_all(format!("{}\0icon\x1f{}\n", caption, image_path).as_bytes())
+            .context("Failed to write to rofi stdin")?;
 
-impl<TCommand: fmt::Display + Clone> RofiCustomKeybindings<TCommand> {
-    pub fn new(select_command: TCommand) -> Self {
-        Self {
-            select_command,
-            keybinds: Vec::new(),
-        }
+        child
+            .wait_with_output()
+            .context("Failed to read rofi stdout")?;
+        Ok(())
     }
+}
 
-    pub fn add(mut self, keybind: &str, command: TCommand) -> Result<Self, Error> {
-        if self.keybinds.len() >= 19 {
-            return Err(anyhow!("Max number of custom rofi keybindings exceeded"));
-        }
-        self.keybinds.push(Keybind {
-            binding: keybind.to_owned().clone(),
-            command,
-        });
-        Ok(self)
-    }
+/// rofi's own default keybindings which can collide with custom keybindings,
+/// from `rofi -dump-config`.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("Control+v", "kb-secondary-paste"),
+    ("Control+V", "kb-primary-paste"),
+    ("Control+w", "kb-clear-line"),
+    ("Control+a", "kb-move-front"),
+    ("Control+e", "kb-move-end"),
+    ("Alt+b", "kb-move-word-back"),
+    ("Alt+f", "kb-move-word-forward"),
+    ("Control+b", "kb-move-char-back"),
+    ("Control+f", "kb-move-char-forward"),
+    ("Control+Alt+h", "kb-remove-word-back"),
+    ("Control+Alt+d", "kb-remove-word-forward"),
+    ("Control+d", "kb-remove-char-forward"),
+    ("Control+h", "kb-remove-char-back"),
+    ("Control+k", "kb-remove-to-eol"),
+    ("Control+u", "kb-remove-to-sol"),
+    ("Control+j", "kb-accept-entry"),
+    ("Control+m", "kb-accept-entry"),
+    ("Control+Return", "kb-accept-custom"),
+    ("Shift+Return", "kb-accept-alt"),
+    ("Shift+Delete", "kb-delete-entry"),
+    ("Shift+Right", "kb-mode-next"),
+    ("Shift+Left", "kb-mode-previous"),
+    ("Control+Tab", "kb-mode-next"),
+    ("Control+l", "kb-mode-complete"),
+    ("Control+p", "kb-row-up"),
+    ("Control+n", "kb-row-down"),
+    ("Control+space", "kb-row-select"),
+    ("Control+g", "kb-cancel"),
+    ("Control+bracketleft", "kb-cancel"),
+    ("Alt+S", "kb-screenshot"),
+    ("Alt+period", "kb-ellipsize"),
+    ("Alt+grave", "kb-toggle-sort"),
+];
 
-    pub fn keybinds(&self) -> &Vec<Keybind<TCommand>> {
-        &self.keybinds
-    }
+/// The rofi action which a keybinding is bound to by default, if any.
+pub fn default_binding_action(binding: &str) -> Option<&'static str> {
+    let binding = launcher::normalize_binding(binding);
+    DEFAULT_BINDINGS
+        .iter()
+        .find(|(default, _)| launcher::normalize_binding(default) == binding)
+        .map(|(_, action)| *action)
+}
 
-    pub fn format_message(&self) -> String {
-        let mut message = String::new();
-        message.push_str(&format!("enter: {}\n", self.select_command));
-        for (i, keybind) in self.keybinds.iter().enumerate() {
-            message.push_str(&match (i, i % 2 == 0) {
-                (0, true) => format!("{:35}", format!("{}: {}", keybind.binding, keybind.command)),
-                (_, true) => format!(
-                    "\n{:35}",
-                    format!("{}: {}", keybind.binding, keybind.command)
-                ),
-                (_, false) => format!("{}: {}", keybind.binding, keybind.command),
-            });
-        }
-        message
-    }
-
-    pub fn exit_code_to_command(&self, code: Option<i32>) -> Option<TCommand> {
-        match code {
-            Some(code) => {
-                if code == 0 {
-                    return Some(self.select_command.clone());
-                }
-                // rofi allows for 19 custom keybindings in total.
-                // rofi signals that a custom keybinding has been used
-                // by a return code between 10 and 28 where:
-                //   -kb-custom-1 corresponds to exit code 10
-                //   -kb-custom-2 corresponds to exit code 11
-                //   -kb-custom-n corresponds to exit code n+9
-                //   -kb-custom-19 corresponds to exit code 28
-                if 10 <= code && code <= 28 {
-                    // custom keybinds are 1-indexed, but our array of
-                    // keybinds, being an array, is obviously 0-indexed
-                    let index = code - 10;
-                    return match self.keybinds.get(index as usize) {
-                        Some(keybind) => Some(keybind.command.clone()),
-                        None => None,
-                    };
-                }
-                None
+fn exit_code_to_command(code: Option<i32>) -> Option<launcher::SelectedCommand> {
+    match code {
+        Some(code) => {
+            if code == 0 {
+                return Some(launcher::SelectedCommand::Select);
+            }
+            // rofi allows for 19 custom keybindings in total.
+            // rofi signals that a custom keybinding has been used
+            // by a return code between 10 and 28 where:
+            //   -kb-custom-1 corresponds to exit code 10
+            //   -kb-custom-2 corresponds to exit code 11
+            //   -kb-custom-n corresponds to exit code n+9
+            //   -kb-custom-19 corresponds to exit code 28
+            if (10..=28).contains(&code) {
+                // custom keybinds are 1-indexed, but our array of
+                // keybinds, being an array, is obviously 0-indexed
+                let index = code - 10;
+                return Some(launcher::SelectedCommand::Custom(index as usize));
             }
-            None => None,
+            None
         }
+        None => None,
     }
 }
 
-#[derive(Debug)]
-pub struct Keybind<TCommand: fmt::Display + Clone> {
-    pub binding: String,
-    pub command: TCommand,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub fn get_passphrase() -> Result<Option<String>, Error> {
-    let passphrase = match String::from_utf8(
-        process::Command::new("rofi")
-            .stdin(process::Stdio::piped())
-            .stdout(process::Stdio::piped())
-            .args(&["-dmenu"])
-            .args(&["-input", "/dev/null"])
-            .args(&["-lines", "0"])
-            .args(&["-width", "20"])
-            .arg("-disable-history")
-            .arg("-password")
-            .args(&["-p", "passphrase"]) // prompt
-            .args(&[
-                "-mesg",
-                "Please enter the passphrase to unlock the OpenPGP secret key",
-            ])
-            .spawn()
-            .context("Failed to spawn rofi")?
-            .wait_with_output()
-            .context("Failed to read rofi stdout")?
-            .stdout,
-    )
-    .context("Failed to read passphrase as utf8")?
-    .trim()
-    {
-        "" => None,
-        val => Some(val.to_owned()),
-    };
-    Ok(passphrase)
-}
+    #[test]
+    fn parse_version() {
+        assert_eq!(
+            "Version: 1.7.5+wayland2\n".parse::<RofiVersion>().unwrap(),
+            RofiVersion(1, 7, 5)
+        );
+        assert_eq!(
+            "1.5.4-60-gdc1a1b6e (makepkg)\n"
+                .parse::<RofiVersion>()
+                .unwrap(),
+            RofiVersion(1, 5, 4)
+        );
+        assert_eq!(
+            "Version: 1.6\n".parse::<RofiVersion>().unwrap(),
+            RofiVersion(1, 6, 0)
+        );
+        assert!("rofi: command not found".parse::<RofiVersion>().is_err());
+    }
 
-// for passwords; custom bindings to allow for generating password?
-pub fn get_new_field_value(prompt: &str, old_value: &str) -> Result<Option<String>, Error> {
-    let new_value = match String::from_utf8(
-        process::Command::new("rofi")
-            .stdin(process::Stdio::piped())
-            .stdout(process::Stdio::piped())
-            .args(&["-dmenu"])
-            .args(&["-input", "/dev/null"])
-            .args(&["-lines", "0"])
-            .args(&["-width", "20"])
-            .arg("-disable-history")
-            .args(&["-p", prompt])
-            .args(&["-filter", old_value])
-            .args(&[
-                "-mesg",
-                "enter to save\nctrl+u to clear\nesc or empty input to cancel",
-            ])
-            .spawn()
-            .context("Failed to spawn rofi")?
-            .wait_with_output()
-            .context("Failed to read rofi stdout")?
-            .stdout,
-    )
-    .context("Failed to read new value as utf8")?
-    .trim()
-    {
-        "" => None,
-        val => Some(val.to_owned()),
-    };
-    Ok(new_value)
+    #[test]
+    fn default_binding_conflicts() {
+        assert_eq!(default_binding_action("ctrl+v"), Some("kb-secondary-paste"));
+        assert_eq!(default_binding_action("alt+e"), None);
+    }
+
+    #[test]
+    fn require_version() {
+        assert!(RofiVersion(1, 4, 2).require(MIN_VERSION, "test").is_err());
+        assert!(RofiVersion(1, 7, 0).require(MIN_VERSION, "test").is_ok());
+    }
 }
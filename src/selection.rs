@@ -0,0 +1,340 @@
+//! Serving the X11 clipboard instead of xclip, which can tell when the copied data is pasted.
+
+use crate::clipboard;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::collections::HashSet;
+use std::sync::atomic;
+use std::sync::Arc;
+use std::thread;
+use std::time;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+const TEXT_TARGETS: &[&[u8]] = &[
+    b"UTF8_STRING",
+    b"STRING",
+    b"TEXT",
+    b"text/plain;charset=utf-8",
+    b"text/plain",
+];
+const PASSWORD_MANAGER_HINT: &[u8] = b"x-kde-passwordManagerHint";
+
+pub struct SelectionOwner {
+    connection: RustConnection,
+    window: xproto::Window,
+    clipboard: xproto::Atom,
+    targets: xproto::Atom,
+    text_targets: Vec<xproto::Atom>,
+    password_manager_hint: xproto::Atom,
+}
+
+impl SelectionOwner {
+    pub fn connect() -> Result<Self, Error> {
+        let (connection, screen_num) =
+            x11rb::connect(None).context("Failed to connect to the X server")?;
+        let root = connection.setup().roots[screen_num].root;
+
+        let window = connection.generate_id()?;
+        connection
+            .create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                xproto::WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &Default::default(),
+            )?
+            .check()
+            .context("Failed to create the selection window")?;
+
+        let atom = |name: &[u8]| -> Result<xproto::Atom, Error> {
+            Ok(connection.intern_atom(false, name)?.reply()?.atom)
+        };
+        let clipboard = atom(b"CLIPBOARD")?;
+        let targets = atom(b"TARGETS")?;
+        let text_targets = TEXT_TARGETS
+            .iter()
+            .map(|name| atom(name))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let password_manager_hint = atom(PASSWORD_MANAGER_HINT)?;
+
+        Ok(Self {
+            connection,
+            window,
+            clipboard,
+            targets,
+            text_targets,
+            password_manager_hint,
+        })
+    }
+
+    fn own(&self) -> Result<(), Error> {
+        self.connection
+            .set_selection_owner(self.window, self.clipboard, x11rb::CURRENT_TIME)?;
+        let owner = self
+            .connection
+            .get_selection_owner(self.clipboard)?
+            .reply()
+            .context("Failed to own the clipboard")?
+            .owner;
+        match owner == self.window {
+            true => Ok(()),
+            false => Err(anyhow!("Failed to own the clipboard")),
+        }
+    }
+
+    /// Leaves the clipboard empty.
+    fn disown(&self) -> Result<(), Error> {
+        self.connection
+            .set_selection_owner(x11rb::NONE, self.clipboard, x11rb::CURRENT_TIME)?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    /// Answers a request for the clipboard, returning whether it pasted the text.
+    fn answer(
+        &self,
+        request: &xproto::SelectionRequestEvent,
+        text: &str,
+        secret: bool,
+    ) -> Result<bool, Error> {
+        // obsolete clients don't name a property
+        let property = match request.property {
+            x11rb::NONE => request.target,
+            property => property,
+        };
+
+        let mut targets = vec![self.targets];
+        targets.extend(&self.text_targets);
+        if secret {
+            targets.push(self.password_manager_hint);
+        }
+
+        let pasted = self.text_targets.contains(&request.target);
+        let property = if request.target == self.targets {
+            self.connection.change_property32(
+                xproto::PropMode::REPLACE,
+                request.requestor,
+                property,
+                xproto::AtomEnum::ATOM,
+                &targets,
+            )?;
+            property
+        } else if pasted {
+            self.connection.change_property8(
+                xproto::PropMode::REPLACE,
+                request.requestor,
+                property,
+                request.target,
+                text.as_bytes(),
+            )?;
+            property
+        } else if secret && request.target == self.password_manager_hint {
+            self.connection.change_property8(
+                xproto::PropMode::REPLACE,
+                request.requestor,
+                property,
+                request.target,
+                b"secret",
+            )?;
+            property
+        } else {
+            x11rb::NONE
+        };
+
+        self.connection.send_event(
+            false,
+            request.requestor,
+            xproto::EventMask::NO_EVENT,
+            xproto::SelectionNotifyEvent {
+                response_type: xproto::SELECTION_NOTIFY_EVENT,
+                sequence: 0,
+                time: request.time,
+                requestor: request.requestor,
+                selection: request.selection,
+                target: request.target,
+                property,
+            },
+        )?;
+        self.connection.flush()?;
+        Ok(pasted && property != x11rb::NONE)
+    }
+}
+
+/// Empties the clipboard, whichever client owns it.
+pub fn clear() -> Result<(), Error> {
+    SelectionOwner::connect()?.disown()
+}
+
+/// Owns the clipboard until something else is copied, calling `ready` once it
+/// holds the copied data. Each paste moves on to the next value of the queue,
+/// and once the data is cleared the previous text is served instead.
+///
+/// Apps ask which targets are offered before pasting, so a read only counts
+/// as a paste after the reader asked for the targets, and not if it asked
+/// for the password manager hint, as clipboard managers do.
+pub fn serve(clip: &clipboard::Clip, ready: &mut dyn FnMut()) -> Result<(), Error> {
+    let owner = SelectionOwner::connect()?;
+    owner.own()?;
+    ready();
+
+    let serving_secret = Arc::new(atomic::AtomicBool::new(true));
+    let expired = Arc::new(atomic::AtomicBool::new(false));
+    if let Some(clear_after) = clip.clear_after {
+        let serving_secret = serving_secret.clone();
+        let expired = expired.clone();
+        thread::spawn(move || -> Result<(), Error> {
+            thread::sleep(time::Duration::from_secs(clear_after));
+            if serving_secret.load(atomic::Ordering::SeqCst) {
+                expired.store(true, atomic::Ordering::SeqCst);
+                // with its own connection, as the other one is waiting for events
+                clear()?;
+            }
+            Ok(())
+        });
+    }
+
+    let copied = clipboard::ClipText::Text(clip.copied.clone());
+    let previous = clip.previous.clone().map(clipboard::ClipText::Text);
+    let mut text = &copied;
+    let mut queue = clip.queue.iter();
+    let mut targets_readers = HashSet::new();
+    let mut hint_readers = HashSet::new();
+    loop {
+        let secret = serving_secret.load(atomic::Ordering::SeqCst);
+        match owner.connection.wait_for_event()? {
+            Event::SelectionRequest(request) => {
+                if request.target == owner.targets {
+                    targets_readers.insert(request.requestor);
+                } else if request.target == owner.password_manager_hint {
+                    hint_readers.insert(request.requestor);
+                }
+                let pasted = owner.answer(&request, &text.text()?, secret)?;
+                if !pasted
+                    || !secret
+                    || !targets_readers.contains(&request.requestor)
+                    || hint_readers.contains(&request.requestor)
+                {
+                    continue;
+                }
+                if let Some(next) = queue.next() {
+                    text = next;
+                } else if clip.paste_once {
+                    serving_secret.store(false, atomic::Ordering::SeqCst);
+                    match &previous {
+                        Some(previous) => text = previous,
+                        None => return owner.disown(),
+                    }
+                }
+            }
+            Event::SelectionClear(_) if secret => {
+                serving_secret.store(false, atomic::Ordering::SeqCst);
+                // something else was copied, rather than the timeout clearing the data
+                if !expired.load(atomic::Ordering::SeqCst) {
+                    return Ok(());
+                }
+                match &previous {
+                    Some(previous) => {
+                        text = previous;
+                        owner.own()?;
+                    }
+                    None => return Ok(()),
+                }
+            }
+            Event::SelectionClear(_) => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otp;
+    use std::sync::mpsc;
+
+    /// Pastes the clipboard as UTF8_STRING after asking for the targets, like an app would.
+    fn paste(paster: &SelectionOwner) -> Option<String> {
+        read(paster, paster.targets)?;
+        read(paster, paster.text_targets[0]).map(|text| String::from_utf8(text).unwrap())
+    }
+
+    fn read(paster: &SelectionOwner, target: xproto::Atom) -> Option<Vec<u8>> {
+        paster
+            .connection
+            .convert_selection(
+                paster.window,
+                paster.clipboard,
+                target,
+                // any atom names the property the data is written to
+                paster.targets,
+                x11rb::CURRENT_TIME,
+            )
+            .unwrap();
+        paster.connection.flush().unwrap();
+        loop {
+            if let Event::SelectionNotify(notify) = paster.connection.wait_for_event().unwrap() {
+                if notify.property == x11rb::NONE {
+                    return None;
+                }
+                let reply = paster
+                    .connection
+                    .get_property(
+                        true,
+                        paster.window,
+                        notify.property,
+                        xproto::AtomEnum::ANY,
+                        0,
+                        u32::MAX,
+                    )
+                    .unwrap()
+                    .reply()
+                    .unwrap();
+                return Some(reply.value);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an X server, run with `xvfb-run cargo test -- --ignored`"]
+    fn paste_queue() {
+        let clip = clipboard::Clip {
+            copied: "alice".to_owned(),
+            queue: vec![
+                clipboard::ClipText::Text("hunter2".to_owned()),
+                clipboard::ClipText::Otp("JBSWY3DPEHPK3PXP".to_owned(), otp::OtpEncoder::Standard),
+            ],
+            previous: None,
+            clear_after: None,
+            paste_once: true,
+        };
+        let (sender, receiver) = mpsc::channel();
+        let server = thread::spawn(move || serve(&clip, &mut || sender.send(()).unwrap()));
+        receiver.recv().unwrap();
+
+        let paster = SelectionOwner::connect().unwrap();
+        // reads the text without asking for the targets or the hint, which isn't a paste
+        let reader = SelectionOwner::connect().unwrap();
+        let read_text =
+            || read(&reader, reader.text_targets[0]).map(|text| String::from_utf8(text).unwrap());
+        assert_eq!(paste(&paster).as_deref(), Some("alice"));
+        assert_eq!(read_text().as_deref(), Some("hunter2"));
+        assert_eq!(paste(&paster).as_deref(), Some("hunter2"));
+        let code = otp::calculate_otp("JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(read_text(), Some(code.clone()));
+        assert_eq!(paste(&paster), Some(code));
+        server.join().unwrap().unwrap();
+        assert_eq!(paste(&paster), None);
+    }
+}
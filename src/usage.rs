@@ -0,0 +1,224 @@
+//! The usage log, which orders the main menu by frecency. It never holds
+//! anything read from an entry.
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Records older than this no longer count towards an entry's score, and are pruned.
+const MAX_AGE: u64 = 90 * DAY;
+
+#[derive(Debug, Clone, PartialEq)]
+struct UsageRecord {
+    timestamp: u64,
+    command: String,
+    pass_store_path: String,
+    entry_path: String,
+}
+
+#[derive(Debug, Default)]
+pub struct UsageLog {
+    path: PathBuf,
+    pass_store_path: String,
+    /// The records of every store.
+    records: Vec<UsageRecord>,
+}
+
+/// The path of a file in `$XDG_STATE_HOME/pass-rofi-gui`.
+pub fn calculate_state_path(file_name: &str) -> Result<PathBuf, Error> {
+    match (env::var("XDG_STATE_HOME"), env::var("HOME")) {
+        (Ok(val), _) if !val.is_empty() => {
+            Ok(PathBuf::from(val).join("pass-rofi-gui").join(file_name))
+        }
+        (_, Ok(val)) => Ok(PathBuf::from(format!(
+            "{}/.local/state/pass-rofi-gui/{}",
+            val, file_name
+        ))),
+        _ => Err(anyhow!(
+            "Can't find state directory! Please set $XDG_STATE_HOME or $HOME"
+        )),
+    }
+}
+
+impl UsageLog {
+    pub fn load(pass_store_path: &str) -> Result<Self, Error> {
+        Self::from_path(&calculate_state_path("usage")?, pass_store_path)
+    }
+
+    fn from_path(log_path: &Path, pass_store_path: &str) -> Result<Self, Error> {
+        let contents = match fs::read_to_string(log_path) {
+            Ok(val) => val,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {:?}", log_path))
+            }
+        };
+
+        Ok(Self {
+            path: log_path.to_owned(),
+            pass_store_path: pass_store_path.trim_end_matches('/').to_owned(),
+            records: Self::parse(&contents),
+        })
+    }
+
+    fn parse(contents: &str) -> Vec<UsageRecord> {
+        contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(4, '\t');
+                match (
+                    parts.next()?.parse().ok(),
+                    parts.next(),
+                    parts.next(),
+                    parts.next(),
+                ) {
+                    (Some(timestamp), Some(command), Some(pass_store_path), Some(entry_path)) => {
+                        Some(UsageRecord {
+                            timestamp,
+                            command: command.to_owned(),
+                            pass_store_path: pass_store_path.to_owned(),
+                            entry_path: entry_path.to_owned(),
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    fn serialize(records: &[UsageRecord]) -> String {
+        records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    record.timestamp, record.command, record.pass_store_path, record.entry_path
+                )
+            })
+            .collect()
+    }
+
+    /// Records a command run on an entry, pruning the store's records of
+    /// entries which no longer exist along the way.
+    pub fn record(&mut self, entry_path: &str, command: &str) -> Result<(), Error> {
+        self.records.push(UsageRecord {
+            timestamp: now()?,
+            command: command.to_owned(),
+            pass_store_path: self.pass_store_path.clone(),
+            entry_path: entry_path.to_owned(),
+        });
+
+        let pass_store = PathBuf::from(&self.pass_store_path);
+        self.retain(|entry_path| pass_store.join(format!("{}.gpg", entry_path)).exists())?;
+        Ok(())
+    }
+
+    /// Removes the records which are too old to count, or are of the store's
+    /// entries which no longer exist, returning how many were removed.
+    pub fn prune(&mut self, entry_paths: &[String]) -> Result<usize, Error> {
+        let entry_paths = entry_paths.iter().collect::<HashSet<_>>();
+        self.retain(|entry_path| entry_paths.contains(&entry_path.to_owned()))
+    }
+
+    /// Keeps the records which still count, and whose entries exist if they
+    /// are of this store, and writes them.
+    fn retain(&mut self, exists: impl Fn(&str) -> bool) -> Result<usize, Error> {
+        let now = now()?;
+        let count = self.records.len();
+        let pass_store_path = &self.pass_store_path;
+        self.records.retain(|record| {
+            now.saturating_sub(record.timestamp) < MAX_AGE
+                && (record.pass_store_path != *pass_store_path || exists(&record.entry_path))
+        });
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        fs::write(&self.path, Self::serialize(&self.records))
+            .with_context(|| format!("Failed to write {:?}", self.path))?;
+        Ok(count - self.records.len())
+    }
+
+    /// Sorts entry paths by their frecency score, and alphabetically among
+    /// equal scores.
+    pub fn sort_by_frecency(&self, entry_paths: &mut [String]) -> Result<(), Error> {
+        let scores = self.scores(now()?);
+        entry_paths.sort_by(|a, b| {
+            let score = |entry_path: &String| scores.get(entry_path.as_str()).copied().unwrap_or(0);
+            score(b).cmp(&score(a)).then_with(|| a.cmp(b))
+        });
+        Ok(())
+    }
+
+    /// The score of every used entry: the sum of its uses, each weighted by
+    /// how recent it is. The weights are coarse, so that the order doesn't
+    /// change while the menu is open.
+    fn scores(&self, now: u64) -> HashMap<&str, u64> {
+        let mut scores = HashMap::new();
+        for record in self
+            .records
+            .iter()
+            .filter(|record| record.pass_store_path == self.pass_store_path)
+        {
+            let weight = match now.saturating_sub(record.timestamp) {
+                age if age < 4 * DAY => 100,
+                age if age < 14 * DAY => 70,
+                age if age < 31 * DAY => 50,
+                age if age < MAX_AGE => 30,
+                _ => 0,
+            };
+            *scores.entry(record.entry_path.as_str()).or_insert(0) += weight;
+        }
+        scores
+    }
+}
+
+fn now() -> Result<u64, Error> {
+    Ok(time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .context("Failed to read the system time")?
+        .as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frecency_order() {
+        let now = now().unwrap();
+        let log = UsageLog {
+            records: UsageLog::parse(&format!(
+                "{recent}\tcopy_password\t{store}\tweb/b\n\
+                 {recent}\tselect\t{store}\tweb/c\n\
+                 {old}\tselect\t{store}\tweb/c\n\
+                 {recent}\tselect\t{store}\tweb/a\n\
+                 {recent}\tselect\t/home/alice/work-store\tweb/d\n\
+                 {recent}\tselect\t/home/alice/work-store\tweb/d\n\
+                 not a record\n",
+                recent = now - DAY,
+                old = now - 20 * DAY,
+                store = "/home/alice/.password-store",
+            )),
+            pass_store_path: "/home/alice/.password-store".to_owned(),
+            ..Default::default()
+        };
+
+        let mut entry_paths = ["web/a", "web/b", "web/c", "web/d", "mail/z"]
+            .iter()
+            .map(|entry_path| entry_path.to_string())
+            .collect::<Vec<_>>();
+        log.sort_by_frecency(&mut entry_paths).unwrap();
+        assert_eq!(entry_paths, ["web/c", "web/a", "web/b", "mail/z", "web/d"]);
+    }
+}
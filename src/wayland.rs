@@ -0,0 +1,311 @@
+//! Wayland, through wl-copy, wtype or ydotool, and grim with slurp. Windows
+//! are only known through the IPC of sway and Hyprland.
+
+use crate::clipboard;
+use crate::display;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use serde_json::Value;
+use std::env;
+use std::io;
+use std::io::Write;
+use std::process;
+
+/// The id of the focused window on compositors without IPC.
+const FOCUSED_WINDOW: &str = "focused";
+
+/// ydotool's key codes, from `linux/input-event-codes.h`, of the keys typed
+/// by autofill.
+const YDOTOOL_KEY_CODES: &[(&str, u32)] = &[
+    ("Escape", 1),
+    ("BackSpace", 14),
+    ("Tab", 15),
+    ("Return", 28),
+    ("space", 57),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compositor {
+    Sway,
+    Hyprland,
+    Other,
+}
+
+#[derive(Debug)]
+pub struct Wayland {
+    compositor: Compositor,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    id: String,
+    /// The title, which is unknown on compositors without IPC.
+    title: Option<String>,
+    class_names: Vec<String>,
+}
+
+impl Wayland {
+    pub fn detect() -> Self {
+        let compositor = if env::var_os("SWAYSOCK").is_some() {
+            Compositor::Sway
+        } else if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            Compositor::Hyprland
+        } else {
+            Compositor::Other
+        };
+        Self { compositor }
+    }
+
+    /// A window by id, or the focused window.
+    fn window(&self, window_id: Option<&str>) -> Result<Window, Error> {
+        let window = match self.compositor {
+            Compositor::Sway => {
+                let tree = serde_json::from_str(&display::command_output(
+                    "swaymsg",
+                    &["-t", "get_tree", "--raw"],
+                )?)
+                .context("Failed to parse swaymsg output")?;
+                find_sway_window(&tree, window_id)
+            }
+            Compositor::Hyprland => {
+                let args: &[&str] = match window_id {
+                    Some(_) => &["clients", "-j"],
+                    None => &["activewindow", "-j"],
+                };
+                let clients: Value =
+                    serde_json::from_str(&display::command_output("hyprctl", args)?)
+                        .context("Failed to parse hyprctl output")?;
+                match &clients {
+                    Value::Array(clients) => clients
+                        .iter()
+                        .find(|client| client["address"].as_str() == window_id),
+                    client => Some(client),
+                }
+                .filter(|client| client["address"].is_string())
+                .map(hyprland_window)
+            }
+            Compositor::Other => Some(Window {
+                id: FOCUSED_WINDOW.to_owned(),
+                ..Default::default()
+            }),
+        };
+
+        window.ok_or_else(|| match window_id {
+            Some(window_id) => anyhow!("Failed to find window {}", window_id),
+            None => anyhow!("Failed to find the focused window"),
+        })
+    }
+
+    /// Focuses a window, which is where wtype and ydotool type.
+    fn focus_window(&self, window_id: &str) -> Result<(), Error> {
+        match self.compositor {
+            Compositor::Sway => {
+                display::run_command("swaymsg", &[&format!("[con_id={}]", window_id), "focus"])?;
+            }
+            Compositor::Hyprland => {
+                display::run_command(
+                    "hyprctl",
+                    &["dispatch", "focuswindow", &format!("address:{}", window_id)],
+                )?;
+            }
+            Compositor::Other => {}
+        }
+        Ok(())
+    }
+
+    fn copy_with(&self, args: &[&str], data: &str) -> Result<(), Error> {
+        let mut wl_copy = process::Command::new("wl-copy")
+            .args(args)
+            .stdin(process::Stdio::piped())
+            .spawn()
+            .context("Failed to spawn wl-copy")?;
+        let stdin = wl_copy
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("Failed to open wl-copy stdin"))?;
+        stdin
+            .write_all(data.as_bytes())
+            .context("Failed to write to wl-copy stdin")?;
+        wl_copy.wait().context("Failed to close wl-copy")?;
+        Ok(())
+    }
+
+    /// Types with wtype, or ydotool if wtype isn't installed, with the
+    /// arguments from `ydotool_args`, which are only worked out for ydotool.
+    fn type_with(
+        &self,
+        wtype_args: &[&str],
+        ydotool_args: impl FnOnce() -> Result<Vec<String>, Error>,
+    ) -> Result<(), Error> {
+        match process::Command::new("wtype").args(wtype_args).status() {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                let status = process::Command::new("ydotool")
+                    .args(ydotool_args()?)
+                    .status()
+                    .context("Failed to exec wtype or ydotool")?;
+                display::check_status("ydotool", status)
+            }
+            status => display::check_status("wtype", status.context("Failed to exec wtype")?),
+        }
+    }
+}
+
+impl display::DisplayServer for Wayland {
+    fn get_active_window_id(&self) -> Result<String, Error> {
+        Ok(self.window(None)?.id)
+    }
+
+    fn get_window_id_by_user_select(&self) -> Result<String, Error> {
+        self.get_active_window_id()
+    }
+
+    fn get_window_name(&self, window_id: &str) -> Result<Option<String>, Error> {
+        Ok(self.window(Some(window_id))?.title)
+    }
+
+    fn get_window_class(&self, window_id: &str) -> Result<Vec<String>, Error> {
+        Ok(self.window(Some(window_id))?.class_names)
+    }
+
+    fn type_string_in_window(&self, window_id: &str, typed_string: &str) -> Result<(), Error> {
+        self.focus_window(window_id)?;
+        self.type_with(&["--", typed_string], || {
+            Ok(vec![
+                "type".to_owned(),
+                "--".to_owned(),
+                typed_string.to_owned(),
+            ])
+        })
+    }
+
+    fn type_key_in_window(&self, window_id: &str, key: &str) -> Result<(), Error> {
+        self.focus_window(window_id)?;
+        self.type_with(&["-k", key], || {
+            // ydotool types key codes rather than names
+            let code = YDOTOOL_KEY_CODES
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, code)| *code)
+                .ok_or_else(|| anyhow!("Unsupported key {} for ydotool", key))?;
+            Ok(vec![
+                "key".to_owned(),
+                format!("{}:1", code),
+                format!("{}:0", code),
+            ])
+        })
+    }
+
+    fn copy_to_clipboard(&self, data: &str) -> Result<(), Error> {
+        self.copy_with(&[], data)
+    }
+
+    fn clear_clipboard(&self) -> Result<(), Error> {
+        display::run_command("wl-copy", &["--clear"])?;
+        Ok(())
+    }
+
+    fn get_clipboard(&self) -> Result<String, Error> {
+        display::command_output("wl-paste", &["--no-newline", "--type", "text"])
+    }
+
+    /// wl-copy can only offer a single type, so the password manager hint
+    /// isn't offered, and it serves a single value, so queues aren't supported.
+    fn serve_clipboard(
+        &self,
+        clip: &clipboard::Clip,
+        ready: &mut dyn FnMut(),
+    ) -> Result<(), Error> {
+        clipboard::check_no_queue(clip)?;
+        match clip.paste_once {
+            true => self.copy_with(&["--paste-once"], &clip.copied)?,
+            false => self.copy_to_clipboard(&clip.copied)?,
+        }
+        ready();
+        clipboard::clear_after_timeout(self, clip)
+    }
+
+    fn capture_screen_region(&self) -> Result<Vec<u8>, Error> {
+        let geometry = display::command_output("slurp", &[])?;
+        display::run_command("grim", &["-g", geometry.trim(), "-t", "png", "-"])
+    }
+}
+
+/// A window in sway's tree, by its con id, or the focused one.
+fn find_sway_window(node: &Value, window_id: Option<&str>) -> Option<Window> {
+    let found = match window_id {
+        Some(window_id) => node["id"].as_u64() == window_id.parse().ok(),
+        None => node["focused"].as_bool() == Some(true),
+    };
+    // containers have no pid, windows do
+    if found && node["pid"].is_number() {
+        let properties = &node["window_properties"];
+        return Some(Window {
+            id: node["id"].to_string(),
+            title: Some(node["name"].as_str().unwrap_or("").to_owned()),
+            // app_id for Wayland windows, and WM_CLASS for XWayland windows
+            class_names: [
+                &properties["instance"],
+                &properties["class"],
+                &node["app_id"],
+            ]
+            .iter()
+            .filter_map(|name| name.as_str())
+            .map(|name| name.to_owned())
+            .collect(),
+        });
+    }
+
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(|child| find_sway_window(child, window_id))
+}
+
+fn hyprland_window(client: &Value) -> Window {
+    Window {
+        id: client["address"].as_str().unwrap_or("").to_owned(),
+        title: Some(client["title"].as_str().unwrap_or("").to_owned()),
+        class_names: [&client["initialClass"], &client["class"]]
+            .iter()
+            .filter_map(|name| name.as_str())
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_owned())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sway_windows() {
+        let tree = serde_json::json!({
+            "id": 1,
+            "nodes": [{
+                "id": 4,
+                "nodes": [
+                    {"id": 7, "pid": 100, "name": "Inbox - Gmail — Mozilla Firefox", "app_id": "firefox", "focused": false},
+                    {"id": 8, "pid": 101, "name": "~", "app_id": null, "focused": true,
+                     "window_properties": {"class": "URxvt", "instance": "urxvt"}},
+                ],
+            }],
+            "floating_nodes": [],
+        });
+
+        let focused = find_sway_window(&tree, None).unwrap();
+        assert_eq!(focused.id, "8");
+        assert_eq!(focused.class_names, ["urxvt", "URxvt"]);
+
+        let firefox = find_sway_window(&tree, Some("7")).unwrap();
+        assert_eq!(
+            firefox.title.as_deref(),
+            Some("Inbox - Gmail — Mozilla Firefox")
+        );
+        assert_eq!(firefox.class_names, ["firefox"]);
+
+        assert!(find_sway_window(&tree, Some("4")).is_none());
+    }
+}
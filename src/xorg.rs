@@ -1,9 +1,66 @@
+use crate::clipboard;
+use crate::display;
+use crate::selection;
 use anyhow::anyhow;
 use anyhow::Context;
 use anyhow::Error;
 use std::io::Write;
 use std::process;
 
+/// X11, through xdotool, xprop, xwininfo, xclip and maim.
+#[derive(Debug)]
+pub struct Xorg;
+
+impl display::DisplayServer for Xorg {
+    fn get_active_window_id(&self) -> Result<String, Error> {
+        get_active_window_id()
+    }
+
+    fn get_window_id_by_user_select(&self) -> Result<String, Error> {
+        get_window_id_by_user_select()
+    }
+
+    fn get_window_name(&self, window_id: &str) -> Result<Option<String>, Error> {
+        get_window_name(window_id).map(Some)
+    }
+
+    fn get_window_class(&self, window_id: &str) -> Result<Vec<String>, Error> {
+        get_window_class(window_id)
+    }
+
+    fn type_string_in_window(&self, window_id: &str, typed_string: &str) -> Result<(), Error> {
+        type_string_in_window(window_id, typed_string)
+    }
+
+    fn type_key_in_window(&self, window_id: &str, key: &str) -> Result<(), Error> {
+        type_key_in_window(window_id, key)
+    }
+
+    fn copy_to_clipboard(&self, data: &str) -> Result<(), Error> {
+        copy_to_clipboard(data)
+    }
+
+    fn clear_clipboard(&self) -> Result<(), Error> {
+        selection::clear()
+    }
+
+    fn get_clipboard(&self) -> Result<String, Error> {
+        display::command_output("xclip", &["-selection", "clipboard", "-out"])
+    }
+
+    fn serve_clipboard(
+        &self,
+        clip: &clipboard::Clip,
+        ready: &mut dyn FnMut(),
+    ) -> Result<(), Error> {
+        selection::serve(clip, ready)
+    }
+
+    fn capture_screen_region(&self) -> Result<Vec<u8>, Error> {
+        capture_screen_region()
+    }
+}
+
 pub fn get_window_id_by_user_select() -> Result<String, Error> {
     let output = process::Command::new("xwininfo")
         .arg("-int")
@@ -20,12 +77,39 @@ pub fn get_window_id_by_user_select() -> Result<String, Error> {
 
     let fragment = line.trim_start_matches("xwininfo: Window id: ");
     let window_id = fragment
-        .split_at(fragment.find(' ').unwrap_or_else(|| fragment.len()))
+        .split_at(fragment.find(' ').unwrap_or(fragment.len()))
         .0;
 
     Ok(window_id.to_owned())
 }
 
+pub fn get_active_window_id() -> Result<String, Error> {
+    Ok(display::command_output("xdotool", &["getactivewindow"])?
+        .trim()
+        .to_owned())
+}
+
+pub fn get_window_name(window_id: &str) -> Result<String, Error> {
+    Ok(
+        display::command_output("xdotool", &["getwindowname", window_id])?
+            .trim_end_matches('\n')
+            .to_owned(),
+    )
+}
+
+/// The instance and class names of a window, from its `WM_CLASS` property.
+pub fn get_window_class(window_id: &str) -> Result<Vec<String>, Error> {
+    let output = display::command_output("xprop", &["-id", window_id, "WM_CLASS"])?;
+
+    // WM_CLASS(STRING) = "Navigator", "firefox"
+    Ok(output
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(|name| name.to_owned())
+        .collect())
+}
+
 pub fn focus_window(window_id: &str) -> Result<(), Error> {
     let status = process::Command::new("xdotool")
         .arg("windowfocus")
@@ -48,7 +132,7 @@ pub fn focus_window(window_id: &str) -> Result<(), Error> {
 pub fn type_key_in_window(window_id: &str, key: &str) -> Result<(), Error> {
     let status = process::Command::new("xdotool")
         .arg("key")
-        .args(&["--window", window_id])
+        .args(["--window", window_id])
         .arg("--clearmodifiers")
         .arg(key)
         .status()
@@ -67,7 +151,7 @@ pub fn type_key_in_window(window_id: &str, key: &str) -> Result<(), Error> {
 
 pub fn type_string_in_window(window_id: &str, typed_string: &str) -> Result<(), Error> {
     let status = process::Command::new("xdotool")
-        .args(&[
+        .args([
             "type",
             "--window",
             window_id,
@@ -91,7 +175,7 @@ pub fn type_string_in_window(window_id: &str, typed_string: &str) -> Result<(),
 pub fn copy_to_clipboard(data: &str) -> Result<(), Error> {
     let mut xclip = process::Command::new("xclip")
         .stdin(process::Stdio::piped())
-        .args(&["-selection", "clip-board"])
+        .args(["-selection", "clip-board"])
         .spawn()
         .context("Failed to spawn xclip")?;
     let stdin = xclip
@@ -104,3 +188,21 @@ pub fn copy_to_clipboard(data: &str) -> Result<(), Error> {
     xclip.wait().context("Failed to close xclip")?;
     Ok(())
 }
+
+pub fn capture_screen_region() -> Result<Vec<u8>, Error> {
+    let output = process::Command::new("maim")
+        .args(["--select", "--hidecursor", "--format", "png"])
+        .output()
+        .context("Failed to exec maim")?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to exec maim: exit code {}",
+            output
+                .status
+                .code()
+                .ok_or_else(|| anyhow!("Failed to get exit code of maim"))?
+        ));
+    };
+    Ok(output.stdout)
+}